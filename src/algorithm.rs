@@ -1,7 +1,11 @@
 use std::{str::FromStr, string::ToString};
 
 use ring::hmac;
-use serde::{Deserialize, Serialize, de::Deserializer, ser::Serializer};
+use serde::{
+	Deserialize, Serialize,
+	de::{self, Deserializer},
+	ser::Serializer,
+};
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
@@ -32,7 +36,7 @@ impl<'de> Deserialize<'de> for Method {
 	where
 		D: Deserializer<'de>,
 	{
-		Ok(Self::from_str(&String::deserialize(deserializer)?).unwrap())
+		Self::from_str(&String::deserialize(deserializer)?).map_err(de::Error::custom)
 	}
 }
 
@@ -50,20 +54,54 @@ impl From<u32> for Method {
 
 impl Method {
 	pub fn is_time_based(self) -> bool {
-		matches!(self, Self::TOTP | Self::Steam)
+		matches!(self, Self::TOTP | Self::Steam | Self::Motp | Self::Yandex)
 	}
 
 	pub fn is_event_based(self) -> bool {
 		matches!(self, Self::HOTP)
 	}
 
-	pub fn to_string(self) -> String {
+	pub fn default_algorithm(self) -> Algorithm {
+		match self {
+			Self::Motp => Algorithm::MD5,
+			Self::Yandex => Algorithm::SHA256,
+			_ => Algorithm::SHA1,
+		}
+	}
+
+	pub fn default_digits(self) -> u32 {
+		match self {
+			Self::Steam => 5,
+			Self::Yandex => 8,
+			_ => 6,
+		}
+	}
+
+	/// The most digits a code can have: the hex digits of MD5 for mOTP, what fits in 64 bits for Yandex
+	pub fn max_digits(self) -> u32 {
+		match self {
+			Self::Motp => 32,
+			Self::Yandex => 13,
+			_ => 10,
+		}
+	}
+
+	pub fn default_period(self) -> Option<u32> {
 		match self {
-			Self::HOTP => "Counter-based".to_string(),
-			Self::TOTP => "Time-based".to_string(),
-			Self::Steam => "Steam".to_string(),
-			Self::Motp => "MOTP".to_string(),
-			Self::Yandex => "Yandex".to_string(),
+			Self::HOTP => None,
+			Self::Motp => Some(10),
+			_ => Some(30),
+		}
+	}
+
+	/// Human-readable name, the wire format is `to_string()`
+	pub fn display_name(self) -> &'static str {
+		match self {
+			Self::HOTP => "Counter-based",
+			Self::TOTP => "Time-based",
+			Self::Steam => "Steam",
+			Self::Motp => "MOTP",
+			Self::Yandex => "Yandex",
 		}
 	}
 }
@@ -77,7 +115,7 @@ impl FromStr for Method {
 			"steam" => Ok(Self::Steam),
 			"motp" => Ok(Self::Motp),
 			"yandex" => Ok(Self::Yandex),
-			_ => anyhow::bail!("Unsupported Method"),
+			_ => anyhow::bail!("Unsupported Method: {s}"),
 		}
 	}
 }
@@ -107,6 +145,8 @@ pub enum Algorithm {
 	SHA256 = 1,
 	// #[enum_value(name = "SHA512")]
 	SHA512 = 2,
+	// Only used by mOTP, which is not HMAC based
+	MD5 = 3,
 }
 
 impl Serialize for Algorithm {
@@ -123,7 +163,7 @@ impl<'de> Deserialize<'de> for Algorithm {
 	where
 		D: Deserializer<'de>,
 	{
-		Ok(Self::from_str(&String::deserialize(deserializer)?).unwrap())
+		Self::from_str(&String::deserialize(deserializer)?).map_err(de::Error::custom)
 	}
 }
 
@@ -133,6 +173,7 @@ impl Algorithm {
 			Self::SHA1 => "SHA1".to_string(),
 			Self::SHA256 => "SHA256".to_string(),
 			Self::SHA512 => "SHA512".to_string(),
+			Self::MD5 => "MD5".to_string(),
 		}
 	}
 }
@@ -144,7 +185,8 @@ impl FromStr for Algorithm {
 			"SHA1" => Ok(Self::SHA1),
 			"SHA256" => Ok(Self::SHA256),
 			"SHA512" => Ok(Self::SHA512),
-			_ => anyhow::bail!("Unsupported HMAC-algorithm"),
+			"MD5" => Ok(Self::MD5),
+			_ => anyhow::bail!("Unsupported HMAC-algorithm: {s}"),
 		}
 	}
 }
@@ -155,17 +197,20 @@ impl ToString for Algorithm {
 			Self::SHA1 => "SHA1",
 			Self::SHA256 => "SHA256",
 			Self::SHA512 => "SHA512",
+			Self::MD5 => "MD5",
 		}
 		.to_string()
 	}
 }
 
-impl From<Algorithm> for hmac::Algorithm {
-	fn from(h: Algorithm) -> Self {
+impl TryFrom<Algorithm> for hmac::Algorithm {
+	type Error = anyhow::Error;
+	fn try_from(h: Algorithm) -> Result<Self, Self::Error> {
 		match h {
-			Algorithm::SHA1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
-			Algorithm::SHA256 => hmac::HMAC_SHA256,
-			Algorithm::SHA512 => hmac::HMAC_SHA512,
+			Algorithm::SHA1 => Ok(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY),
+			Algorithm::SHA256 => Ok(hmac::HMAC_SHA256),
+			Algorithm::SHA512 => Ok(hmac::HMAC_SHA512),
+			Algorithm::MD5 => anyhow::bail!("MD5 is not an HMAC-algorithm"),
 		}
 	}
 }
@@ -175,7 +220,37 @@ impl From<u32> for Algorithm {
 		match u {
 			1 => Self::SHA256,
 			2 => Self::SHA512,
+			3 => Self::MD5,
 			_ => Self::default(),
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn method_serde() {
+		let methods = [Method::TOTP, Method::HOTP, Method::Steam, Method::Motp, Method::Yandex];
+		for method in methods {
+			let json = serde_json::to_string(&method).unwrap();
+			assert_eq!(json, format!("\"{}\"", method.to_string()));
+			assert_eq!(serde_json::from_str::<Method>(&json).unwrap(), method);
+		}
+		assert_eq!(serde_json::to_string(&Method::TOTP).unwrap(), "\"totp\"");
+		assert_eq!(Method::TOTP.to_string(), "totp");
+		assert_eq!(Method::TOTP.display_name(), "Time-based");
+		assert!(serde_json::from_str::<Method>("\"Time-based\"").is_err());
+	}
+
+	#[test]
+	fn algorithm_serde() {
+		for algorithm in [Algorithm::SHA1, Algorithm::SHA256, Algorithm::SHA512, Algorithm::MD5] {
+			let json = serde_json::to_string(&algorithm).unwrap();
+			assert_eq!(serde_json::from_str::<Algorithm>(&json).unwrap(), algorithm);
+		}
+		let err = serde_json::from_str::<Algorithm>("\"SHA3\"").unwrap_err();
+		assert!(err.to_string().contains("SHA3"));
+	}
+}
@@ -0,0 +1,248 @@
+//! andOTP Import/Export Module
+//!
+//! andOTP backups are a JSON array of entries, either plain or encrypted:
+//! 4 bytes iteration count (big endian), 12 bytes salt, 12 bytes nonce, then the AES-GCM ciphertext and tag.
+//! The key is derived with PBKDF2-HMAC-SHA1, see:
+//! <https://github.com/andOTP/andOTP/blob/master/app/src/main/java/org/shadowice/flocke/andotp/Utilities/EncryptionHelper.java>
+
+use aes_gcm::{KeyInit, aead::Aead};
+use anyhow::{Context, Result, anyhow, bail};
+use rand::{Rng, RngCore};
+use ring::pbkdf2;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::algorithm::{Algorithm, Method};
+use crate::uri::split_label;
+use crate::vault::{Database, Entry};
+
+const ITERATIONS_MIN: u32 = 140_000;
+const ITERATIONS_MAX: u32 = 160_000;
+const SALT_LEN: usize = 12;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 4 + SALT_LEN + NONCE_LEN;
+
+#[derive(Debug, Default, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct AndOtpEntry {
+	secret: String,
+	#[serde(default)]
+	#[zeroize(skip)]
+	issuer: String,
+	#[serde(default)]
+	#[zeroize(skip)]
+	label: String,
+	#[zeroize(skip)]
+	digits: u32,
+	#[serde(rename = "type")]
+	#[zeroize(skip)]
+	otp_type: String,
+	#[zeroize(skip)]
+	algorithm: Algorithm,
+	#[serde(default)]
+	#[zeroize(skip)]
+	thumbnail: String,
+	#[serde(default)]
+	#[zeroize(skip)]
+	last_used: u64,
+	#[serde(default)]
+	#[zeroize(skip)]
+	used_frequency: u64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[zeroize(skip)]
+	period: Option<u32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[zeroize(skip)]
+	counter: Option<u32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pin: Option<String>,
+	#[serde(default)]
+	#[zeroize(skip)]
+	tags: Vec<String>,
+}
+
+/// Whether the data is an encrypted andOTP backup (plain backups are a JSON array)
+pub fn is_encrypted(data: &[u8]) -> bool {
+	!data.trim_ascii_start().starts_with(b"[")
+}
+
+/// Read a plain or encrypted andOTP backup, tags become groups
+pub fn parse(data: &[u8], password: Option<&str>) -> Result<Database> {
+	let json = if is_encrypted(data) {
+		decrypt(data, password.context("The andOTP backup is encrypted, a password is needed")?)?
+	} else {
+		Zeroizing::new(data.to_vec())
+	};
+	let items: Vec<AndOtpEntry> = serde_json::from_slice(&json).context("Not an andOTP backup")?;
+
+	let mut db = Database::default();
+	for item in items {
+		let (issuer, label) = if item.issuer.is_empty() {
+			// Older versions of andOTP kept the issuer in the label
+			match split_label(&item.label) {
+				(Some(issuer), label) => (issuer.to_string(), label.to_string()),
+				(None, label) => match label.split_once(" - ") {
+					Some((issuer, label)) => (issuer.to_string(), label.to_string()),
+					None => (String::new(), label.to_string()),
+				},
+			}
+		} else {
+			(item.issuer.clone(), item.label.clone())
+		};
+		let mut entry = Entry { label, issuer: Some(issuer).filter(|issuer| !issuer.is_empty()), ..Default::default() };
+		entry.method = match item.otp_type.parse()? {
+			Method::Yandex => bail!("Unsupported andOTP type {} for {}", item.otp_type, entry.label),
+			method => method,
+		};
+		entry.info.algorithm = item.algorithm;
+		entry.info.digits = item.digits;
+		entry.info.pin = item.pin.clone();
+		if entry.method.is_event_based() {
+			entry.info.counter = Some(item.counter.unwrap_or(0));
+		} else {
+			entry.info.period = item.period;
+		}
+		if entry.method == Method::Motp {
+			// andOTP keeps the hexadecimal text of mOTP secrets
+			let text = Zeroizing::new(
+				data_encoding::BASE32_NOPAD
+					.decode(canonical(&item.secret).as_bytes())
+					.map_err(|_| anyhow!("Secret is not valid base32"))?,
+			);
+			entry.info.set_hex_secret(std::str::from_utf8(&text)?).with_context(|| format!("Invalid secret for {}", entry.label))?;
+		} else {
+			entry.info.set_secret(&item.secret).with_context(|| format!("Invalid secret for {}", entry.label))?;
+		}
+		entry.groups = item.tags.iter().map(|tag| db.group_uuid(tag)).collect();
+		entry.apply_defaults()?;
+		db.entries.push(entry);
+	}
+	Ok(db)
+}
+
+/// Write the entries as an andOTP backup, encrypted when a password is given, groups become tags
+///
+/// Yandex entries are skipped with a warning, andOTP does not support them.
+pub fn export(db: &Database, password: Option<&str>) -> Result<Vec<u8>> {
+	let mut items = Vec::new();
+	for entry in &db.entries {
+		if entry.method == Method::Yandex {
+			eprintln!("Skipping {}:{}: andOTP does not support Yandex entries", entry.issuer(), entry.label());
+			continue;
+		}
+		let secret = if entry.method == Method::Motp {
+			data_encoding::BASE32_NOPAD.encode(hex::encode(&*entry.info.secret_bytes()?).as_bytes())
+		} else {
+			entry.secret()
+		};
+		items.push(AndOtpEntry {
+			secret,
+			issuer: entry.issuer(),
+			label: entry.label(),
+			digits: entry.info.digits,
+			otp_type: entry.method.to_string().to_uppercase(),
+			algorithm: entry.info.algorithm,
+			thumbnail: "Default".to_string(),
+			period: entry.info.period.filter(|_| entry.method.is_time_based()),
+			counter: entry.info.counter.filter(|_| entry.method.is_event_based()),
+			pin: entry.info.pin.clone(),
+			tags: db.group_names(entry),
+			..Default::default()
+		});
+	}
+	let json = Zeroizing::new(serde_json::to_vec_pretty(&items)?);
+	match password {
+		Some(password) => encrypt(&json, password),
+		None => Ok(json.to_vec()),
+	}
+}
+
+fn canonical(secret: &str) -> String {
+	secret.chars().filter(|c| !matches!(c, ' ' | '-' | '=')).collect::<String>().to_uppercase()
+}
+
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> Result<Zeroizing<[u8; 32]>> {
+	let iterations = NonZeroU32::new(iterations).context("Invalid iteration count")?;
+	let mut key = Zeroizing::new([0u8; 32]);
+	pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA1, iterations, salt, password.as_bytes(), key.as_mut());
+	Ok(key)
+}
+
+fn decrypt(data: &[u8], password: &str) -> Result<Zeroizing<Vec<u8>>> {
+	if data.len() < HEADER_LEN {
+		bail!("The andOTP backup is too short");
+	}
+	let iterations = u32::from_be_bytes(data[..4].try_into()?);
+	let (salt, nonce) = (&data[4..4 + SALT_LEN], &data[4 + SALT_LEN..HEADER_LEN]);
+	let key = derive_key(password, salt, iterations)?;
+	let cipher = aes_gcm::Aes256Gcm::new_from_slice(key.as_ref()).map_err(|_| anyhow!("Could not create cipher from key"))?;
+	let plaintext = cipher
+		.decrypt(aes_gcm::Nonce::from_slice(nonce), &data[HEADER_LEN..])
+		.map_err(|_| anyhow!("Cannot decrypt the andOTP backup. Wrong password?"))?;
+	Ok(Zeroizing::new(plaintext))
+}
+
+fn encrypt(json: &[u8], password: &str) -> Result<Vec<u8>> {
+	let mut rng = rand::rng();
+	let iterations = rng.random_range(ITERATIONS_MIN..ITERATIONS_MAX);
+	let mut header = [0u8; HEADER_LEN];
+	header[..4].copy_from_slice(&iterations.to_be_bytes());
+	rng.fill_bytes(&mut header[4..]);
+	let key = derive_key(password, &header[4..4 + SALT_LEN], iterations)?;
+	let cipher = aes_gcm::Aes256Gcm::new_from_slice(key.as_ref()).map_err(|_| anyhow!("Could not create cipher from key"))?;
+	let ciphertext = cipher
+		.encrypt(aes_gcm::Nonce::from_slice(&header[4 + SALT_LEN..]), json)
+		.map_err(|_| anyhow!("Encrypting the andOTP backup"))?;
+	Ok([header.as_slice(), &ciphertext].concat())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BACKUP: &str = r#"[
+		{"secret":"JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP","issuer":"GitHub","label":"alice","digits":6,"type":"TOTP","algorithm":"SHA1","thumbnail":"Default","last_used":0,"used_frequency":0,"period":30,"tags":["Work"]},
+		{"secret":"JBSWY3DPEHPK3PXP","label":"Corp - bob","digits":8,"type":"HOTP","algorithm":"SHA256","counter":5,"tags":["Work","Old"]},
+		{"secret":"JRZCL47CMXVOQMNPZR2F7J4RGI","issuer":"Steam","label":"carol","digits":5,"type":"STEAM","algorithm":"SHA1","period":30,"tags":[]}
+	]"#;
+
+	#[test]
+	fn parse_plain() {
+		let db = parse(BACKUP.as_bytes(), None).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].period(), Some(30));
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Work"]);
+		assert_eq!(db.entries[1].issuer(), "Corp");
+		assert_eq!(db.entries[1].label(), "bob");
+		assert_eq!(db.entries[1].method(), Method::HOTP);
+		assert_eq!(db.entries[1].algorithm(), Algorithm::SHA256);
+		assert_eq!(db.entries[1].counter(), Some(5));
+		assert_eq!(db.entries[1].digits(), Some(8));
+		assert_eq!(db.group_names(&db.entries[1]), vec!["Work", "Old"]);
+		assert_eq!(db.entries[2].method(), Method::Steam);
+		assert_eq!(db.groups.len(), 2);
+
+		assert!(parse(b"[{}]", None).is_err());
+		assert!(parse(b"not andotp", None).is_err());
+	}
+
+	#[test]
+	fn encrypted_round_trip() {
+		let db = parse(BACKUP.as_bytes(), None).unwrap();
+		let backup = export(&db, Some("secret")).unwrap();
+		assert!(is_encrypted(&backup));
+		assert!(parse(&backup, Some("wrong")).is_err());
+		assert!(parse(&backup, None).is_err());
+
+		let restored = parse(&backup, Some("secret")).unwrap();
+		assert_eq!(restored.entries.len(), 3);
+		assert_eq!(restored.entries[1].label(), "bob");
+		assert_eq!(restored.entries[1].secret(), "JBSWY3DPEHPK3PXP");
+		assert_eq!(restored.group_names(&restored.entries[1]), vec!["Work", "Old"]);
+
+		let plain = String::from_utf8(export(&db, None).unwrap()).unwrap();
+		assert!(plain.contains(r#""type": "HOTP""#));
+		assert!(plain.contains(r#""counter": 5"#));
+	}
+}
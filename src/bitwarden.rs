@@ -0,0 +1,110 @@
+//! Bitwarden Import Module
+//!
+//! Unencrypted Bitwarden JSON exports have a list of `items`, logins keep their TOTP seed in `login.totp`:
+//! a bare base32 secret, an otpauth URI or `steam://SECRET`. Encrypted exports are not supported.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::algorithm::Method;
+use crate::uri;
+use crate::vault::{Database, Entry};
+
+#[derive(Debug, Deserialize)]
+struct Export {
+	#[serde(default)]
+	encrypted: bool,
+	#[serde(default)]
+	items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Item {
+	#[serde(default)]
+	name: String,
+	#[serde(default)]
+	notes: Option<String>,
+	#[serde(default)]
+	login: Option<Login>,
+}
+
+#[derive(Debug, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct Login {
+	#[serde(default)]
+	#[zeroize(skip)]
+	username: Option<String>,
+	#[serde(default)]
+	totp: Option<String>,
+}
+
+/// Read the TOTP entries of an unencrypted Bitwarden JSON export
+///
+/// The item name becomes the issuer and the username the label (when set), items without TOTP are skipped.
+pub fn parse(data: &[u8]) -> Result<Database> {
+	let export: Export = serde_json::from_slice(data).context("Not a Bitwarden JSON export")?;
+	if export.encrypted {
+		bail!("Encrypted Bitwarden exports are not supported, export as unencrypted JSON");
+	}
+
+	let mut db = Database::default();
+	for item in &export.items {
+		let Some(login) = &item.login else { continue };
+		let Some(totp) = login.totp.as_deref().map(str::trim).filter(|totp| !totp.is_empty()) else { continue };
+		let mut entry = if totp.starts_with("otpauth://") {
+			uri::parse(totp).with_context(|| format!("Invalid TOTP URI for {}", item.name))?
+		} else if let Some(secret) = totp.strip_prefix("steam://") {
+			let mut entry = Entry { method: Method::Steam, ..Default::default() };
+			entry.info.set_secret(secret).with_context(|| format!("Invalid Steam secret for {}", item.name))?;
+			entry
+		} else {
+			let mut entry = Entry::default();
+			entry.info.set_secret(totp).with_context(|| format!("Invalid TOTP secret for {}", item.name))?;
+			entry
+		};
+		if !item.name.is_empty() {
+			entry.issuer = Some(item.name.clone());
+		}
+		if let Some(username) = login.username.as_ref().filter(|username| !username.is_empty()) {
+			entry.label = username.clone();
+		}
+		entry.note = item.notes.clone().unwrap_or_default();
+		entry.apply_defaults()?;
+		db.entries.push(entry);
+	}
+	eprintln!("Found {} TOTP entries in {} Bitwarden items.", db.entries.len(), export.items.len());
+	Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::algorithm::Algorithm;
+
+	#[test]
+	fn parse_export() {
+		let export = r#"{"encrypted": false, "folders": [], "items": [
+			{"type": 1, "name": "GitHub", "notes": "Work account", "login": {"username": "alice", "password": "hunter2", "totp": "jbsw y3dp ehpk 3pxp jbsw y3dp ehpk 3pxp"}},
+			{"type": 1, "name": "Corp", "login": {"username": "", "totp": "otpauth://totp/Corp:bob?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&digits=8"}},
+			{"type": 1, "name": "Steam", "login": {"username": "carol", "totp": "steam://JRZCL47CMXVOQMNPZR2F7J4RGI"}},
+			{"type": 1, "name": "No TOTP", "login": {"username": "dave", "totp": null}},
+			{"type": 2, "name": "Secure note", "notes": "text"}
+		]}"#;
+		let db = parse(export.as_bytes()).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].label(), "alice");
+		assert_eq!(db.entries[0].secret(), "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP");
+		assert_eq!(db.entries[0].note, "Work account");
+		assert_eq!(db.entries[0].period(), Some(30));
+		assert_eq!(db.entries[1].issuer(), "Corp");
+		assert_eq!(db.entries[1].label(), "bob");
+		assert_eq!(db.entries[1].algorithm(), Algorithm::SHA256);
+		assert_eq!(db.entries[1].digits(), Some(8));
+		assert_eq!(db.entries[2].method(), Method::Steam);
+		assert_eq!(db.entries[2].digits(), Some(5));
+
+		assert!(parse(br#"{"encrypted": true, "data": "..."}"#).is_err());
+		assert!(parse(br#"{"items": [{"name": "Bad", "login": {"totp": "not base32!"}}]}"#).is_err());
+	}
+}
@@ -0,0 +1,638 @@
+//! Command Line Module
+//!
+//! The `aegisvault` command, run with a format registry so formats added to it are offered by `--from` and `--to`.
+
+use crate::{
+	files::file_name,
+	format::{Options, Registry},
+	icon, migration, otp, qr, uri,
+	vault::{Aegis, Database, Entry, VaultLock},
+};
+use anyhow::{Context, Result};
+use clap::builder::styling::{AnsiColor, Effects, Styles};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use rpassword::read_password;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write, stdin, stdout};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+// Cargo's color style: https://github.com/crate-ci/clap-cargo/blob/master/src/style.rs
+const STYLE: Styles = Styles::styled()
+	.header(AnsiColor::Green.on_default().effects(Effects::BOLD))
+	.usage(AnsiColor::Green.on_default().effects(Effects::BOLD))
+	.literal(AnsiColor::Cyan.on_default().effects(Effects::BOLD))
+	.placeholder(AnsiColor::Cyan.on_default())
+	.error(AnsiColor::Red.on_default().effects(Effects::BOLD))
+	.valid(AnsiColor::Cyan.on_default().effects(Effects::BOLD))
+	.invalid(AnsiColor::Yellow.on_default().effects(Effects::BOLD));
+
+#[derive(Parser, Debug)]
+#[clap(version, about, styles = STYLE, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true, help_template(
+	"\
+{before-help}{name} {version} - {about}
+{usage-heading} {usage}
+{all-args}{after-help}
+"
+))]
+struct Cli {
+	#[command(subcommand)]
+	command: Option<Command>,
+	/// The otpauth-URI inputfile, or a PNG/JPEG image (or directory of them) with QR codes, or a backup in the --from format
+	#[arg(required = true)]
+	uri_file: Option<PathBuf>,
+	/// The format of the inputfile [default: detected from the content]
+	#[arg(long)]
+	from: Option<String>,
+	/// The format of the output
+	#[arg(long, default_value = "aegis")]
+	to: String,
+	/// The directory to write to, for --to pass
+	#[arg(short, long)]
+	out_dir: Option<PathBuf>,
+	/// The CSV columns, for --from csv and --to csv, like: issuer,label,secret,digits,period,algorithm,type,counter,note,group
+	#[arg(short, long)]
+	columns: Option<String>,
+	/// A setting of the --from or --to format, like columns=issuer,label,secret (can be repeated)
+	#[arg(long, value_name = "NAME=VALUE", value_parser = setting)]
+	set: Vec<(String, String)>,
+	/// Add all entries to this group (can be repeated)
+	#[arg(short, long)]
+	group: Vec<String>,
+	/// Attach the icons in this directory named after the issuer (like github.svg) to the entries without icon
+	#[arg(long)]
+	icons: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+	/// Show the live codes of the entries in an Aegis vault
+	Show {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// Only show entries with this in their issuer or label
+		filter: Option<String>,
+	},
+	/// Print the current code of an entry in an Aegis vault
+	Code {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The UUID of the entry, or text in its issuer or label
+		entry: String,
+		/// HOTP only: write the incremented counter back into the vault
+		#[arg(short, long)]
+		next: bool,
+	},
+	/// Check whether a code is valid for an entry in an Aegis vault
+	Check {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The UUID of the entry, or text in its issuer or label
+		entry: String,
+		/// The code to check
+		code: String,
+		/// Number of periods before and after now (or counters after the stored counter for HOTP) to accept, at most 100
+		#[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(..=otp::MAX_WINDOW))]
+		window: u64,
+	},
+	/// Show entries of an Aegis vault as QR codes, one at a time, or write them as images
+	Qr {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The UUID of an entry, or text in the issuer or label of the entries (default: all)
+		entry: Option<String>,
+		/// Write one image per entry into this directory (secrets are only written to disk with this option)
+		#[arg(short, long)]
+		out_dir: Option<PathBuf>,
+		/// The image format for --out-dir
+		#[arg(short, long, value_enum, default_value_t = ImageFormat::Png)]
+		format: ImageFormat,
+	},
+	/// Export entries of an Aegis vault as Google Authenticator migration URIs or QR codes
+	Google {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The UUID of an entry, or text in the issuer or label of the entries (default: all)
+		entry: Option<String>,
+		/// Number of entries per migration URI (more makes the QR codes harder to scan)
+		#[arg(short, long, default_value_t = migration::BATCH_SIZE)]
+		batch_size: usize,
+		/// Show the QR codes in the terminal, one at a time, instead of printing the URIs
+		#[arg(short, long)]
+		qr: bool,
+		/// Write one image per QR code into this directory
+		#[arg(short, long, conflicts_with = "qr")]
+		out_dir: Option<PathBuf>,
+		/// The image format for --out-dir
+		#[arg(short, long, value_enum, default_value_t = ImageFormat::Png)]
+		format: ImageFormat,
+	},
+	/// Manage the groups of an Aegis vault
+	Group {
+		#[command(subcommand)]
+		command: GroupCommand,
+	},
+	/// Attach, extract or strip the icons of the entries in an Aegis vault
+	Icon {
+		#[command(subcommand)]
+		command: IconCommand,
+	},
+}
+
+#[derive(Subcommand, Debug)]
+enum IconCommand {
+	/// Attach an SVG, PNG or JPEG image as icon to entries
+	Attach {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The UUID of an entry, or text in the issuer or label of the entries
+		entry: String,
+		/// The image file
+		image: PathBuf,
+	},
+	/// Write the icons of all entries into a directory
+	Extract {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The directory to write to
+		out_dir: PathBuf,
+	},
+	/// Remove the icons of entries, to shrink the vault
+	Strip {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		/// The UUID of an entry, or text in the issuer or label of the entries (default: all)
+		entry: Option<String>,
+	},
+}
+
+#[derive(Subcommand, Debug)]
+enum GroupCommand {
+	/// List the groups with their number of entries
+	List {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+	},
+	/// Create a group
+	Add {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		name: String,
+	},
+	/// Rename a group
+	Rename {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		name: String,
+		new_name: String,
+	},
+	/// Delete a group, its entries are kept
+	Delete {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		name: String,
+	},
+	/// Add entries to a group, the group is created when missing
+	Assign {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		name: String,
+		/// The UUID of an entry, or text in the issuer or label of the entries
+		entry: String,
+	},
+	/// Remove entries from a group
+	Unassign {
+		/// The Aegis vault JSON file
+		vault: PathBuf,
+		name: String,
+		/// The UUID of an entry, or text in the issuer or label of the entries
+		entry: String,
+	},
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ImageFormat {
+	Png,
+	Svg,
+}
+
+impl ImageFormat {
+	fn extension(self) -> &'static str {
+		match self {
+			Self::Png => "png",
+			Self::Svg => "svg",
+		}
+	}
+}
+
+/// Run the command line with the arguments of the process
+pub fn run(registry: Registry) -> Result<()> {
+	let formats =
+		|formats: Vec<(&'static str, &'static str)>| PossibleValuesParser::new(formats.into_iter().map(|(name, help)| PossibleValue::new(name).help(help)));
+	let importers = formats(registry.importers().map(|importer| (importer.name(), importer.description())).collect());
+	let exporters = formats(registry.exporters().map(|exporter| (exporter.name(), exporter.description())).collect());
+	let matches = Cli::command()
+		.mut_arg("from", |arg| arg.value_parser(importers))
+		.mut_arg("to", |arg| arg.value_parser(exporters))
+		.get_matches();
+	let arg = Cli::from_arg_matches(&matches)?;
+	match arg.command {
+		Some(Command::Show { vault, filter }) => show(&vault, filter.as_deref()),
+		Some(Command::Code { vault, entry, next }) => code(&vault, &entry, next),
+		Some(Command::Check { vault, entry, code, window }) => check(&vault, &entry, &code, window),
+		Some(Command::Qr { vault, entry, out_dir, format }) => qr(&vault, entry.as_deref(), out_dir.as_deref(), format),
+		Some(Command::Google { vault, entry, batch_size, qr, out_dir, format }) => {
+			google(&registry, &vault, entry.as_deref(), batch_size, qr, out_dir.as_deref(), format)
+		}
+		Some(Command::Group { command }) => group(command),
+		Some(Command::Icon { command }) => icon(command),
+		None => {
+			let input = arg.uri_file.context("Missing URI_FILE")?;
+			let mut settings: HashMap<String, String> = arg.set.into_iter().collect();
+			if let Some(columns) = arg.columns {
+				settings.insert("columns".to_string(), columns);
+			}
+			let options = Options { out_dir: arg.out_dir, settings, ..Default::default() };
+			convert(&registry, &input, arg.from.as_deref(), &arg.to, options, &arg.group, arg.icons.as_deref())
+		}
+	}
+}
+
+/// Parse a `NAME=VALUE` format setting
+fn setting(arg: &str) -> Result<(String, String), String> {
+	arg.split_once('=')
+		.map(|(name, value)| (name.to_string(), value.to_string()))
+		.ok_or_else(|| format!("expected NAME=VALUE, found '{arg}'"))
+}
+
+fn prompt_password(prompt: &str) -> Result<String> {
+	eprint!("{prompt}");
+	stdout().flush()?;
+	Ok(read_password()?)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+	std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))
+}
+
+fn convert(registry: &Registry, input: &Path, from: Option<&str>, to: &str, mut options: Options, groups: &[String], icons: Option<&Path>) -> Result<()> {
+	let data = if input.is_dir() { Vec::new() } else { read_file(input)? };
+	let importer = match from {
+		Some(from) => registry.importer(from).with_context(|| format!("Unknown input format {from}"))?,
+		None => {
+			let importer = registry.detect(input, &data).with_context(|| format!("Cannot detect the format of {}, use --from", input.display()))?;
+			eprintln!("Detected {} (--from {})", importer.description(), importer.name());
+			importer
+		}
+	};
+	let exporter = registry.exporter(to).with_context(|| format!("Unknown output format {to}"))?;
+
+	if importer.needs_password(&data) {
+		options.password = Some(Zeroizing::new(prompt_password(&format!("Password of the {} input: ", importer.name()))?));
+	}
+	let mut db = importer.import(input, &data, &options)?;
+	for name in groups {
+		let uuid = db.group_uuid(name);
+		for entry in db.entries.iter_mut().filter(|entry| !entry.groups.contains(&uuid)) {
+			entry.groups.push(uuid.clone());
+		}
+	}
+	if let Some(icons) = icons {
+		eprintln!("Attached icons to {} entries", icon::attach_dir(&mut db.entries, icons)?);
+	}
+	options.password = None;
+	if exporter.needs_password() {
+		options.password = Some(Zeroizing::new(prompt_password(&format!("Password to be set on the {} output: ", exporter.name()))?));
+	}
+	stdout().write_all(&exporter.export(db, &options)?)?;
+	Ok(())
+}
+
+/// The master key of an encrypted vault, to seal it again after a change
+type MasterKey = Zeroizing<Vec<u8>>;
+
+/// Read the entries of an Aegis vault, asking for the password when it is encrypted
+fn read_vault(path: &Path) -> Result<Vec<Entry>> {
+	Ok(open_vault(path, None)?.0.into_database()?.entries)
+}
+
+/// Open an Aegis vault, returning the master key when it was encrypted
+///
+/// The password is asked for when the vault is encrypted and no `password` is given.
+fn open_vault(path: &Path, password: Option<&str>) -> Result<(Aegis, Option<MasterKey>)> {
+	let data = read_file(path)?;
+	let mut vault: Aegis = serde_json::from_slice(&data).context("Not an Aegis vault")?;
+	let master_key = match (&vault, password) {
+		(Aegis::Plaintext(_), _) => None,
+		(Aegis::Encrypted(_), Some(password)) => Some(vault.decrypt(password)?),
+		(Aegis::Encrypted(_), None) => Some(vault.decrypt(&prompt_password("Password of the Aegis vault: ")?)?),
+	};
+	Ok((vault, master_key))
+}
+
+/// Lock an Aegis vault and open it for modification
+///
+/// The password is asked for before taking the lock, so other invocations do not wait for the typing.
+fn lock_vault(path: &Path) -> Result<(VaultLock, Aegis, Option<MasterKey>)> {
+	let password = match serde_json::from_slice(&read_file(path)?).context("Not an Aegis vault")? {
+		Aegis::Encrypted(_) => Some(Zeroizing::new(prompt_password("Password of the Aegis vault: ")?)),
+		Aegis::Plaintext(_) => None,
+	};
+	let lock = VaultLock::acquire(path)?;
+	let (vault, master_key) = open_vault(path, password.as_deref().map(String::as_str))?;
+	Ok((lock, vault, master_key))
+}
+
+/// Seal the vault again if it was encrypted and write it back
+fn save_vault(path: &Path, mut vault: Aegis, master_key: Option<MasterKey>) -> Result<()> {
+	if let Some(master_key) = master_key {
+		vault.seal(&master_key)?;
+	}
+	vault.write_atomic(path)
+}
+
+/// Find the entry with the given UUID, or the only entry with the query in its issuer or label
+fn select_entry(entries: &[Entry], query: &str) -> Result<usize> {
+	if let Some(index) = entries.iter().position(|entry| entry.uuid == query) {
+		return Ok(index);
+	}
+	let found: Vec<usize> = (0..entries.len()).filter(|&index| entries[index].matches(query)).collect();
+	match found[..] {
+		[index] => Ok(index),
+		[] => anyhow::bail!("No entry matches '{query}'"),
+		_ => {
+			let names: Vec<String> = found.iter().map(|&index| format!("{}:{}", entries[index].issuer(), entries[index].label())).collect();
+			anyhow::bail!("'{query}' matches several entries: {}", names.join(", "))
+		}
+	}
+}
+
+fn code(path: &Path, query: &str, next: bool) -> Result<()> {
+	let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+	if !next {
+		let entries = read_vault(path)?;
+		let entry = &entries[select_entry(&entries, query)?];
+		println!("{}", otp::generate(entry, time)?);
+		return Ok(());
+	}
+
+	// Hold the lock from reading until writing, so no counter is used twice
+	let (_lock, mut vault, master_key) = lock_vault(path)?;
+	let entries = &mut vault.database_mut()?.entries;
+	let index = select_entry(entries, query)?;
+	let entry = &mut entries[index];
+	if !entry.method.is_event_based() {
+		anyhow::bail!("--next only applies to HOTP entries, {} is {}", entry.label(), entry.method.display_name());
+	}
+	let code = otp::generate(entry, time)?;
+	entry.info.counter = Some(entry.counter().unwrap_or_default().checked_add(1).context("Counter overflow")?);
+	save_vault(path, vault, master_key)?;
+	println!("{code}");
+	Ok(())
+}
+
+fn check(path: &Path, query: &str, code: &str, window: u64) -> Result<()> {
+	let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+	let entries = read_vault(path)?;
+	let entry = &entries[select_entry(&entries, query)?];
+	match otp::verify_code(entry, code, time, window)? {
+		Some(counter) if entry.method.is_event_based() => println!("Valid for counter {counter}, the next counter is {}", counter + 1),
+		Some(_) => println!("Valid"),
+		None => anyhow::bail!("Invalid code for {}", entry.label()),
+	}
+	Ok(())
+}
+
+fn group(command: GroupCommand) -> Result<()> {
+	let (GroupCommand::List { vault: path }
+	| GroupCommand::Add { vault: path, .. }
+	| GroupCommand::Rename { vault: path, .. }
+	| GroupCommand::Delete { vault: path, .. }
+	| GroupCommand::Assign { vault: path, .. }
+	| GroupCommand::Unassign { vault: path, .. }) = &command;
+	let (_lock, mut vault, master_key) = lock_vault(path)?;
+	let db = vault.database_mut()?;
+	match &command {
+		GroupCommand::List { .. } => {
+			for group in &db.groups {
+				let count = db.entries.iter().filter(|entry| entry.groups.contains(&group.uuid)).count();
+				println!("{}: {count} entries", group.name);
+			}
+			return Ok(());
+		}
+		GroupCommand::Add { name, .. } => {
+			db.add_group(name)?;
+		}
+		GroupCommand::Rename { name, new_name, .. } => db.rename_group(name, new_name)?,
+		GroupCommand::Delete { name, .. } => db.delete_group(name)?,
+		GroupCommand::Assign { name, entry, .. } | GroupCommand::Unassign { name, entry, .. } => {
+			let assign = matches!(command, GroupCommand::Assign { .. });
+			for index in select_entries(path, &db.entries, Some(entry))? {
+				let title = format!("{}:{}", db.entries[index].issuer(), db.entries[index].label());
+				match assign {
+					true if db.assign_group(index, name)? => eprintln!("Added {title} to {name}"),
+					false if db.unassign_group(index, name)? => eprintln!("Removed {title} from {name}"),
+					_ => (),
+				}
+			}
+		}
+	}
+	save_vault(path, vault, master_key)
+}
+
+fn icon(command: IconCommand) -> Result<()> {
+	let (path, query) = match &command {
+		IconCommand::Extract { vault, out_dir } => {
+			for file in icon::extract(&read_vault(vault)?, out_dir)? {
+				eprintln!("Written {}", file.display());
+			}
+			return Ok(());
+		}
+		IconCommand::Attach { vault, entry, .. } => (vault, Some(entry.as_str())),
+		IconCommand::Strip { vault, entry } => (vault, entry.as_deref()),
+	};
+	let (_lock, mut vault, master_key) = lock_vault(path)?;
+	let entries = &mut vault.database_mut()?.entries;
+	for index in select_entries(path, entries, query)? {
+		let entry = &mut entries[index];
+		match &command {
+			IconCommand::Attach { image, .. } => {
+				entry.set_icon(&read_file(image)?).with_context(|| format!("Invalid icon {}", image.display()))?;
+				eprintln!("Attached {} to {}:{}", image.display(), entry.issuer(), entry.label());
+			}
+			_ if entry.thumbnail.is_some() => {
+				entry.strip_icon();
+				eprintln!("Removed the icon of {}:{}", entry.issuer(), entry.label());
+			}
+			_ => (),
+		}
+	}
+	save_vault(path, vault, master_key)
+}
+
+/// A QR code to show or write, with its title and file name
+struct QrCode {
+	title: String,
+	name: String,
+	uri: String,
+}
+
+/// The indices of the entry with UUID `query`, or of the entries with `query` in their issuer or label (default: all)
+///
+/// Entries are selected by index, imported entries can share an empty UUID.
+fn select_entries(path: &Path, entries: &[Entry], query: Option<&str>) -> Result<Vec<usize>> {
+	let indices: Vec<usize> = match query {
+		Some(query) => match entries.iter().position(|entry| entry.uuid == query) {
+			Some(index) => vec![index],
+			None => (0..entries.len()).filter(|&index| entries[index].matches(query)).collect(),
+		},
+		None => (0..entries.len()).collect(),
+	};
+	if indices.is_empty() {
+		anyhow::bail!("No matching entries in {}", path.display());
+	}
+	Ok(indices)
+}
+
+fn qr(path: &Path, query: Option<&str>, out_dir: Option<&Path>, format: ImageFormat) -> Result<()> {
+	let entries = read_vault(path)?;
+	let codes = select_entries(path, &entries, query)?
+		.into_iter()
+		.map(|index| {
+			let entry = &entries[index];
+			Ok(QrCode {
+				title: format!("{}: {}", entry.issuer(), entry.label()),
+				name: file_name(&format!("{}_{}", entry.issuer(), entry.label())),
+				uri: uri::build(entry)?,
+			})
+		})
+		.collect::<Result<Vec<_>>>()?;
+	match out_dir {
+		Some(out_dir) => write_qr_codes(&codes, out_dir, format),
+		None => show_qr_codes(&codes),
+	}
+}
+
+/// Export entries with the `google` exporter of the registry, or show its URIs as QR codes
+fn google(registry: &Registry, path: &Path, query: Option<&str>, batch_size: usize, show: bool, out_dir: Option<&Path>, format: ImageFormat) -> Result<()> {
+	let exporter = registry.exporter("google").context("Unknown output format google")?;
+	let entries = read_vault(path)?;
+	let selected = select_entries(path, &entries, query)?;
+	let entries = entries.into_iter().enumerate().filter(|(index, _)| selected.contains(index)).map(|(_, entry)| entry).collect();
+	let settings = HashMap::from([("batch-size".to_string(), batch_size.to_string()), ("format".to_string(), format.extension().to_string())]);
+	let options = Options { out_dir: out_dir.map(Path::to_path_buf), settings, ..Default::default() };
+	let output = Zeroizing::new(String::from_utf8(exporter.export(Database { entries, ..Default::default() }, &options)?)?);
+	if !show {
+		print!("{}", output.as_str());
+		return Ok(());
+	}
+	let count = output.lines().count();
+	let codes: Vec<QrCode> = output
+		.lines()
+		.enumerate()
+		.map(|(n, uri)| QrCode {
+			title: format!("Google Authenticator {} of {count}", n + 1),
+			name: format!("google-{}-of-{count}", n + 1),
+			uri: uri.to_string(),
+		})
+		.collect();
+	show_qr_codes(&codes)
+}
+
+/// Show QR codes in the terminal, waiting for Enter between them
+fn show_qr_codes(codes: &[QrCode]) -> Result<()> {
+	for (n, code) in codes.iter().enumerate() {
+		println!("{}\n{}", code.title, qr::terminal(&code.uri)?);
+		if n + 1 < codes.len() {
+			eprint!("Press Enter for the next QR code ({} of {})", n + 1, codes.len());
+			stdin().read_line(&mut String::new())?;
+		}
+	}
+	Ok(())
+}
+
+/// Write QR codes as images into `out_dir`, keeping the file names unique
+fn write_qr_codes(codes: &[QrCode], out_dir: &Path, format: ImageFormat) -> Result<()> {
+	for file in qr::write_images(codes.iter().map(|code| (code.name.as_str(), code.uri.as_str())), out_dir, format.extension())? {
+		eprintln!("Written {}", file.display());
+	}
+	Ok(())
+}
+
+fn show(path: &Path, filter: Option<&str>) -> Result<()> {
+	let entries: Vec<Entry> = read_vault(path)?.into_iter().filter(|entry| filter.is_none_or(|filter| entry.matches(filter))).collect();
+	if entries.is_empty() {
+		anyhow::bail!("No matching entries in {}", path.display());
+	}
+	let issuer_width = entries.iter().map(|entry| entry.issuer().chars().count()).chain([6]).max().unwrap_or_default();
+	let label_width = entries.iter().map(|entry| entry.label().chars().count()).chain([5]).max().unwrap_or_default();
+	// Redraw the table every second on a terminal, print it once otherwise
+	let terminal = stdout().is_terminal();
+	loop {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+		let time = now.as_secs();
+		let mut screen = if terminal { "\x1b[H\x1b[2J".to_string() } else { String::new() };
+		screen += &format!("{:issuer_width$}  {:label_width$}  {:10}  Next\n", "Issuer", "Label", "Code");
+		for entry in &entries {
+			let (code, next) = match otp::generate(entry, time) {
+				Ok(code) => match (otp::remaining(entry, time), entry.period()) {
+					(Some(left), Some(period)) => (code, progress_bar(left, u64::from(period))),
+					_ => (code, format!("counter {}", entry.counter().unwrap_or_default())),
+				},
+				Err(e) => ("-".to_string(), format!("{e}")),
+			};
+			screen += &format!("{:issuer_width$}  {:label_width$}  {code:10}  {next}\n", entry.issuer(), entry.label());
+		}
+		print!("{screen}");
+		stdout().flush()?;
+		if !terminal {
+			return Ok(());
+		}
+		sleep(Duration::from_secs(1) - Duration::from_nanos(now.subsec_nanos().into()));
+	}
+}
+
+/// A bar that empties towards the next period boundary
+fn progress_bar(left: u64, period: u64) -> String {
+	const WIDTH: u64 = 20;
+	let filled = (left * WIDTH).div_ceil(period) as usize;
+	format!("[{:<width$}] {left:>2}s", "#".repeat(filled), width = WIDTH as usize)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn window_limit() {
+		let check = |window: &str| Cli::try_parse_from(["aegisvault", "check", "vault.json", "alice", "123456", "--window", window]);
+		assert!(check("100").is_ok());
+		assert!(check("101").is_err());
+		assert!(check("18446744073709551615").is_err());
+	}
+
+	#[test]
+	fn icon_without_uuids() {
+		// The icon commands on an imported vault without UUIDs must touch the named entry only
+		let dir = std::env::temp_dir().join(format!("aegisvault-main-icon-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		let (path, image) = (dir.join("vault.json"), dir.join("icon.svg"));
+		std::fs::write(&image, "<svg xmlns=\"http://www.w3.org/2000/svg\"/>").unwrap();
+		let mut vault = Aegis::default();
+		vault.add_entry(Entry { label: "alice".to_string(), ..Default::default() });
+		vault.add_entry(Entry { label: "bob".to_string(), ..Default::default() });
+		vault.write_atomic(&path).unwrap();
+		let icons = || read_vault(&path).unwrap().iter().map(|entry| entry.thumbnail.is_some()).collect::<Vec<_>>();
+
+		icon(IconCommand::Attach { vault: path.clone(), entry: "bob".to_string(), image: image.clone() }).unwrap();
+		assert_eq!(icons(), vec![false, true]);
+		icon(IconCommand::Attach { vault: path.clone(), entry: "alice".to_string(), image }).unwrap();
+		icon(IconCommand::Strip { vault: path.clone(), entry: Some("alice".to_string()) }).unwrap();
+		assert_eq!(icons(), vec![false, true]);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,263 @@
+//! CSV Import/Export Module
+//!
+//! Each row is an entry, the meaning of the columns is given by a column list like
+//! `issuer,label,secret,digits,period,algorithm,type,counter,note,group` (`-` skips a column).
+//! A first row consisting of column names is recognised as header and used as column list
+//! when none is given. Multiple groups in the group column are separated by `;`.
+
+use anyhow::{Context, Result, bail};
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+use crate::algorithm::{Algorithm, Method};
+use crate::vault::{Database, Entry};
+
+/// The columns written by default
+pub const DEFAULT_COLUMNS: &str = "issuer,label,secret,digits,period,algorithm,type,counter,note,group";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+	Issuer,
+	Label,
+	Secret,
+	Digits,
+	Period,
+	Algorithm,
+	Type,
+	Counter,
+	Note,
+	Group,
+	Pin,
+}
+
+impl FromStr for Column {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_ref() {
+			"issuer" => Ok(Self::Issuer),
+			"label" | "name" | "account" => Ok(Self::Label),
+			"secret" => Ok(Self::Secret),
+			"digits" => Ok(Self::Digits),
+			"period" => Ok(Self::Period),
+			"algorithm" | "algo" => Ok(Self::Algorithm),
+			"type" => Ok(Self::Type),
+			"counter" => Ok(Self::Counter),
+			"note" | "notes" => Ok(Self::Note),
+			"group" | "groups" => Ok(Self::Group),
+			"pin" => Ok(Self::Pin),
+			_ => bail!("Unknown column: {s}"),
+		}
+	}
+}
+
+impl Column {
+	fn name(self) -> &'static str {
+		match self {
+			Self::Issuer => "issuer",
+			Self::Label => "label",
+			Self::Secret => "secret",
+			Self::Digits => "digits",
+			Self::Period => "period",
+			Self::Algorithm => "algorithm",
+			Self::Type => "type",
+			Self::Counter => "counter",
+			Self::Note => "note",
+			Self::Group => "group",
+			Self::Pin => "pin",
+		}
+	}
+}
+
+/// Parse a comma separated column list, `-` (or an empty name) skips a column
+pub fn parse_columns(list: &str) -> Result<Vec<Option<Column>>> {
+	let columns = list
+		.split(',')
+		.map(|name| match name.trim() {
+			"" | "-" => Ok(None),
+			name => name.parse().map(Some),
+		})
+		.collect::<Result<Vec<_>>>()?;
+	if !columns.contains(&Some(Column::Secret)) {
+		bail!("The columns need to include secret");
+	}
+	Ok(columns)
+}
+
+/// Read the entries of a CSV file, with `columns` or else the header row (or else [`DEFAULT_COLUMNS`])
+///
+/// All rows are validated first, the errors of all invalid rows are reported together.
+pub fn parse(data: &[u8], columns: Option<&[Option<Column>]>) -> Result<Database> {
+	let mut reader = ::csv::ReaderBuilder::new().has_headers(false).flexible(true).trim(::csv::Trim::All).from_reader(data);
+	let mut records = reader.records().peekable();
+	let header = match records.peek() {
+		Some(Ok(first)) => first.iter().all(|field| field.is_empty() || field == "-" || field.parse::<Column>().is_ok()).then(|| first.clone()),
+		_ => None,
+	};
+	if header.is_some() {
+		records.next();
+	}
+	let columns = match (columns, &header) {
+		(Some(columns), _) => columns.to_vec(),
+		(None, Some(header)) => parse_columns(&header.iter().collect::<Vec<_>>().join(","))?,
+		(None, None) => parse_columns(DEFAULT_COLUMNS)?,
+	};
+
+	let mut db = Database::default();
+	let mut errors = Vec::new();
+	for record in records {
+		let (line, row) = match record {
+			Ok(record) => (record.position().map(::csv::Position::line), row_entry(&mut db, &columns, &record)),
+			Err(e) => (e.position().map(::csv::Position::line), Err(e.into())),
+		};
+		match row {
+			Ok(Some(entry)) => db.entries.push(entry),
+			Ok(None) => (),
+			Err(e) => errors.push(format!("Line {}: {e:#}", line.unwrap_or_default())),
+		}
+	}
+	if !errors.is_empty() {
+		bail!("{} invalid rows:\n{}", errors.len(), errors.join("\n"));
+	}
+	Ok(db)
+}
+
+/// The entry of a row, `None` for an empty row
+fn row_entry(db: &mut Database, columns: &[Option<Column>], record: &::csv::StringRecord) -> Result<Option<Entry>> {
+	if record.iter().all(str::is_empty) {
+		return Ok(None);
+	}
+	let field = |column: Column| columns.iter().position(|c| *c == Some(column)).and_then(|i| record.get(i)).filter(|field| !field.is_empty());
+
+	let mut entry = Entry {
+		method: field(Column::Type).map(str::parse).transpose()?.unwrap_or_default(),
+		issuer: field(Column::Issuer).map(ToOwned::to_owned),
+		label: field(Column::Label).unwrap_or_default().to_string(),
+		note: field(Column::Note).unwrap_or_default().to_string(),
+		..Default::default()
+	};
+	if let Some(algorithm) = field(Column::Algorithm) {
+		entry.info.algorithm = algorithm.parse::<Algorithm>()?;
+	}
+	if let Some(digits) = field(Column::Digits) {
+		entry.info.digits = digits.parse().with_context(|| format!("Invalid digits: {digits}"))?;
+		entry.info.check_digits(entry.method)?;
+	}
+	if entry.method.is_event_based() {
+		entry.info.counter = Some(field(Column::Counter).map(str::parse).transpose().context("Invalid counter")?.unwrap_or(0));
+	} else if let Some(period) = field(Column::Period) {
+		entry.info.period = Some(period.parse().with_context(|| format!("Invalid period: {period}"))?);
+		if entry.info.period == Some(0) {
+			bail!("Invalid period: 0");
+		}
+	}
+	entry.info.pin = field(Column::Pin).map(ToOwned::to_owned);
+	let secret = Zeroizing::new(field(Column::Secret).context("Missing secret")?.to_string());
+	match entry.method {
+		Method::Motp => entry.info.set_hex_secret(&secret)?,
+		Method::Yandex => entry.info.set_yandex_secret(&secret)?,
+		_ => entry.info.set_secret(&secret)?,
+	}
+	if entry.method == Method::Yandex && entry.info.pin.is_none() {
+		bail!("Missing pin for Yandex entry");
+	}
+	if let Some(groups) = field(Column::Group) {
+		entry.groups = groups.split(';').map(str::trim).filter(|name| !name.is_empty()).map(|name| db.group_uuid(name)).collect();
+	}
+	entry.apply_defaults()?;
+	Ok(Some(entry))
+}
+
+/// Write the entries as CSV with a header row, in the order of `columns`
+pub fn export(db: &Database, columns: &[Option<Column>]) -> Result<String> {
+	let mut writer = ::csv::Writer::from_writer(Vec::new());
+	writer.write_record(columns.iter().map(|column| column.map_or("-", Column::name)))?;
+	for entry in &db.entries {
+		let mut row = Vec::with_capacity(columns.len());
+		for column in columns {
+			let info = &entry.info;
+			row.push(match column {
+				None => String::new(),
+				Some(Column::Issuer) => entry.issuer(),
+				Some(Column::Label) => entry.label(),
+				Some(Column::Secret) if entry.method == Method::Motp => hex::encode(&*info.secret_bytes()?),
+				Some(Column::Secret) => entry.secret(),
+				Some(Column::Digits) => info.digits.to_string(),
+				Some(Column::Period) => info.period.filter(|_| entry.method.is_time_based()).map(|period| period.to_string()).unwrap_or_default(),
+				Some(Column::Algorithm) => info.algorithm.to_string(),
+				Some(Column::Type) => entry.method.to_string(),
+				Some(Column::Counter) => info.counter.filter(|_| entry.method.is_event_based()).map(|counter| counter.to_string()).unwrap_or_default(),
+				Some(Column::Note) => entry.note.clone(),
+				Some(Column::Group) => db.group_names(entry).join(";"),
+				Some(Column::Pin) => entry.pin().unwrap_or_default(),
+			});
+		}
+		writer.write_record(&row)?;
+	}
+	Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn header_detection() {
+		let csv = "Label,Issuer,Secret,Notes,Groups\n\
+			alice,GitHub,JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP,\"Work, main account\",Work;Dev\n\
+			\n\
+			bob,\"Corp \"\"Inc\"\"\",JBSWY3DPEHPK3PXP,,\n";
+		let db = parse(csv.as_bytes(), None).unwrap();
+		assert_eq!(db.entries.len(), 2);
+		assert_eq!(db.entries[0].label(), "alice");
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].note, "Work, main account");
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Work", "Dev"]);
+		assert_eq!(db.entries[1].issuer(), "Corp \"Inc\"");
+		assert_eq!(db.entries[1].period(), Some(30));
+	}
+
+	#[test]
+	fn columns() {
+		let columns = parse_columns("secret,-,label,type,counter,digits").unwrap();
+		let csv = "JBSWY3DPEHPK3PXP,ignored,bob,hotp,5,8\n";
+		let db = parse(csv.as_bytes(), Some(&columns)).unwrap();
+		assert_eq!(db.entries[0].label(), "bob");
+		assert_eq!(db.entries[0].method(), Method::HOTP);
+		assert_eq!(db.entries[0].counter(), Some(5));
+		assert_eq!(db.entries[0].digits(), Some(8));
+
+		let db = parse(b"JBSWY3DPEHPK3PXP\n", Some(&parse_columns("secret").unwrap())).unwrap();
+		assert_eq!(db.entries[0].method(), Method::TOTP);
+
+		assert!(parse_columns("label,issuer").is_err());
+		assert!(parse_columns("secret,colour").is_err());
+	}
+
+	#[test]
+	fn validation() {
+		let csv = "issuer,label,secret,digits,period,type\n\
+			A,a,JBSWY3DPEHPK3PXP,6,30,totp\n\
+			B,b,not base32!,6,30,totp\n\
+			C,c,JBSWY3DPEHPK3PXP,six,30,totp\n\
+			D,d,JBSWY3DPEHPK3PXP,6,30,sms\n\
+			E,e,JBSWY3DPEHPK3PXP,0,30,totp\n\
+			F,f,JBSWY3DPEHPK3PXP,11,30,steam\n";
+		let error = format!("{:#}", parse(csv.as_bytes(), None).unwrap_err());
+		assert!(error.starts_with("5 invalid rows"));
+		assert!(error.contains("Line 3:"));
+		assert!(error.contains("Line 4: Invalid digits: six"));
+		assert!(error.contains("Line 5:"));
+		assert!(error.contains("Line 6: Invalid digits 0 for totp"));
+		assert!(error.contains("Line 7: Invalid digits 11 for steam"));
+	}
+
+	#[test]
+	fn round_trip() {
+		let csv = "issuer,label,secret,digits,period,algorithm,type,counter,note,group\n\
+			GitHub,alice,JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP,6,30,SHA1,totp,,\"Line 1\nLine 2\",Work\n\
+			Corp,bob,JBSWY3DPEHPK3PXP,8,,SHA256,hotp,7,,\n";
+		let db = parse(csv.as_bytes(), None).unwrap();
+		let exported = export(&db, &parse_columns(DEFAULT_COLUMNS).unwrap()).unwrap();
+		assert_eq!(exported, csv);
+	}
+}
@@ -0,0 +1,80 @@
+//! File Module
+//!
+//! Vaults, exports and QR codes contain plain secrets, so they are written only readable by the user.
+//! Files named after entries get their names cleaned up and made unique here.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Create a new file only readable by the user, fails when `path` exists
+pub fn create(path: &Path) -> std::io::Result<File> {
+	let mut options = std::fs::OpenOptions::new();
+	options.write(true).create_new(true);
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+	options.open(path)
+}
+
+/// Write `contents` to a file only readable by the user, replacing an existing file
+pub fn write(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+	match std::fs::remove_file(path) {
+		Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+		_ => {}
+	}
+	create(path)?.write_all(contents)
+}
+
+/// Replace the characters that do not belong in a file name with `_`
+pub fn file_name(name: &str) -> String {
+	name.chars().map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '@') { c } else { '_' }).collect()
+}
+
+/// Keeps names unique by adding `-2`, `-3`, ... to the names already taken
+#[derive(Debug, Default)]
+pub struct UniqueNames(HashSet<String>);
+
+impl UniqueNames {
+	pub fn unique(&mut self, name: &str) -> String {
+		let mut unique = name.to_string();
+		for n in 2.. {
+			if self.0.insert(unique.clone()) {
+				break;
+			}
+			unique = format!("{name}-{n}");
+		}
+		unique
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(unix)]
+	fn private() {
+		use std::os::unix::fs::PermissionsExt;
+		let path = std::env::temp_dir().join(format!("aegisvault-files-{}", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		create(&path).unwrap();
+		assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+		assert!(create(&path).is_err());
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+		write(&path, b"secret").unwrap();
+		assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+		assert_eq!(std::fs::read(&path).unwrap(), b"secret");
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn names() {
+		assert_eq!(file_name("ACME Co_john.doe@example.com/x"), "ACME_Co_john.doe@example.com_x");
+		let mut names = UniqueNames::default();
+		assert_eq!(names.unique("a"), "a");
+		assert_eq!(names.unique("a"), "a-2");
+		assert_eq!(names.unique("a"), "a-3");
+		assert_eq!(names.unique("b"), "b");
+	}
+}
@@ -0,0 +1,754 @@
+//! Import/Export Format Registry
+//!
+//! Every input format is an [`Importer`] and every output format an [`Exporter`]. A [`Registry`] holds the
+//! built-in formats and can be extended with others, the `--from` and `--to` lists of the command line come from it.
+//! Importers are sniffed in registry order, so the built-in ones are tried before added ones.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::vault::{Aegis, Database};
+use crate::{andotp, bitwarden, csv, freeotp, icon, keepass, migration, pass, proton, qr, twofas, uri};
+
+/// The settings an importer or exporter may use
+#[derive(Default)]
+pub struct Options {
+	/// The password, set when [`Importer::needs_password`] or [`Exporter::needs_password`] says so
+	pub password: Option<Zeroizing<String>>,
+	/// The directory to write to, for formats writing several files
+	pub out_dir: Option<PathBuf>,
+	/// Format specific settings by name, like `columns` for CSV
+	pub settings: HashMap<String, String>,
+}
+
+impl Options {
+	pub fn setting(&self, name: &str) -> Option<&str> {
+		self.settings.get(name).map(String::as_str)
+	}
+}
+
+/// An input format
+pub trait Importer {
+	/// The name of the format, as used for `--from`
+	fn name(&self) -> &'static str;
+
+	/// A short description of the format
+	fn description(&self) -> &'static str;
+
+	/// The usual file extensions (without dot)
+	fn extensions(&self) -> &'static [&'static str] {
+		&[]
+	}
+
+	/// Whether reading `data` needs a password
+	fn needs_password(&self, _data: &[u8]) -> bool {
+		false
+	}
+
+	/// Whether the input looks like this format, `data` is empty for a directory
+	fn sniff(&self, path: &Path, data: &[u8]) -> bool;
+
+	/// Read the entries, `data` is the content of the file at `path` (empty for a directory)
+	fn import(&self, path: &Path, data: &[u8], options: &Options) -> Result<Database>;
+}
+
+/// An output format
+pub trait Exporter {
+	/// The name of the format, as used for `--to`
+	fn name(&self) -> &'static str;
+
+	/// A short description of the format
+	fn description(&self) -> &'static str;
+
+	/// The usual file extensions (without dot)
+	fn extensions(&self) -> &'static [&'static str] {
+		&[]
+	}
+
+	/// Whether writing needs a password
+	fn needs_password(&self) -> bool {
+		false
+	}
+
+	/// Write the entries, returns the output (empty when the files are written to [`Options::out_dir`])
+	fn export(&self, db: Database, options: &Options) -> Result<Vec<u8>>;
+}
+
+/// The known importers and exporters
+pub struct Registry {
+	importers: Vec<Box<dyn Importer>>,
+	exporters: Vec<Box<dyn Exporter>>,
+}
+
+impl Default for Registry {
+	/// A registry with the built-in formats
+	fn default() -> Self {
+		let mut registry = Self::empty();
+		registry
+			.add_importer(Uri)
+			.add_importer(AegisVault)
+			.add_importer(AndOtp)
+			.add_importer(TwoFas)
+			.add_importer(FreeOtpPlus)
+			.add_importer(FreeOtp)
+			.add_importer(Bitwarden)
+			.add_importer(KeePassCsv)
+			.add_importer(KeePassXml)
+			.add_importer(Pass)
+			.add_importer(Proton)
+			.add_importer(Csv);
+		registry
+			.add_exporter(AegisVault)
+			.add_exporter(AndOtp)
+			.add_exporter(KeePassCsv)
+			.add_exporter(KeePassXml)
+			.add_exporter(Pass)
+			.add_exporter(Csv)
+			.add_exporter(Google);
+		registry
+	}
+}
+
+impl Registry {
+	/// A registry without any format
+	pub fn empty() -> Self {
+		Self { importers: Vec::new(), exporters: Vec::new() }
+	}
+
+	/// Add an importer, replacing the one with the same name
+	pub fn add_importer(&mut self, importer: impl Importer + 'static) -> &mut Self {
+		match self.importers.iter().position(|known| known.name() == importer.name()) {
+			Some(index) => self.importers[index] = Box::new(importer),
+			None => self.importers.push(Box::new(importer)),
+		}
+		self
+	}
+
+	/// Add an exporter, replacing the one with the same name
+	pub fn add_exporter(&mut self, exporter: impl Exporter + 'static) -> &mut Self {
+		match self.exporters.iter().position(|known| known.name() == exporter.name()) {
+			Some(index) => self.exporters[index] = Box::new(exporter),
+			None => self.exporters.push(Box::new(exporter)),
+		}
+		self
+	}
+
+	pub fn importers(&self) -> impl Iterator<Item = &dyn Importer> {
+		self.importers.iter().map(Box::as_ref)
+	}
+
+	pub fn exporters(&self) -> impl Iterator<Item = &dyn Exporter> {
+		self.exporters.iter().map(Box::as_ref)
+	}
+
+	pub fn importer(&self, name: &str) -> Option<&dyn Importer> {
+		self.importers().find(|importer| importer.name() == name)
+	}
+
+	pub fn exporter(&self, name: &str) -> Option<&dyn Exporter> {
+		self.exporters().find(|exporter| exporter.name() == name)
+	}
+
+	/// The first importer recognising the input, `data` is the content of the file at `path` (empty for a directory)
+	pub fn detect(&self, path: &Path, data: &[u8]) -> Option<&dyn Importer> {
+		self.importers().find(|importer| importer.sniff(path, data))
+	}
+}
+
+/// The JSON value of `data`, when it is a JSON object or array
+fn json(data: &[u8]) -> Option<serde_json::Value> {
+	let data = data.trim_ascii();
+	if data.starts_with(b"{") || data.starts_with(b"[") { serde_json::from_slice(data).ok() } else { None }
+}
+
+/// Whether `data` is a JSON object with one of `keys`
+fn json_with_key(data: &[u8], keys: &[&str]) -> bool {
+	json(data).is_some_and(|json| keys.iter().any(|key| json.get(key).is_some()))
+}
+
+fn text(data: &[u8]) -> Option<&str> {
+	std::str::from_utf8(data.trim_ascii()).ok().filter(|text| !text.is_empty())
+}
+
+/// The fields of the first line of a CSV file (quotes removed)
+fn csv_header(data: &[u8]) -> Vec<&str> {
+	let line = text(data).and_then(|text| text.lines().next()).unwrap_or_default();
+	line.split(',').map(|field| field.trim().trim_matches('"')).collect()
+}
+
+/// Whether `data` is a PNG or JPEG image, by its magic bytes or else by the extension of `path`
+fn is_image(path: &Path, data: &[u8]) -> bool {
+	matches!(icon::mime(data), Some(icon::PNG | icon::JPEG))
+		|| path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.is_some_and(|ext| ["png", "jpg", "jpeg"].contains(&ext.to_lowercase().as_str()))
+}
+
+fn images(dir: &Path) -> Result<Vec<PathBuf>> {
+	let mut images: Vec<PathBuf> = std::fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+	images.retain(|image| is_image(image, &file_start(image)));
+	images.sort();
+	Ok(images)
+}
+
+/// The first bytes of a file, enough for its magic bytes
+fn file_start(path: &Path) -> Vec<u8> {
+	let mut start = Vec::new();
+	if let Ok(file) = std::fs::File::open(path) {
+		let _ = file.take(8).read_to_end(&mut start);
+	}
+	start
+}
+
+fn password(options: &Options) -> Option<&str> {
+	options.password.as_deref().map(String::as_str)
+}
+
+struct Uri;
+
+impl Importer for Uri {
+	fn name(&self) -> &'static str {
+		"uri"
+	}
+	fn description(&self) -> &'static str {
+		"otpauth URIs, one per line (like Ente Auth plaintext exports), or QR code images"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["txt", "png", "jpg", "jpeg"]
+	}
+	fn sniff(&self, path: &Path, data: &[u8]) -> bool {
+		if path.is_dir() {
+			return images(path).is_ok_and(|images| !images.is_empty());
+		}
+		is_image(path, data)
+			|| text(data).is_some_and(|text| {
+				text.lines()
+					.map(str::trim)
+					.filter(|line| !line.is_empty())
+					.all(|line| line.starts_with("otpauth://") || line.starts_with("otpauth-migration:"))
+			})
+	}
+	/// Read the entries from an otpauth-URI file, a QR code image or a directory of images
+	fn import(&self, path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		let mut db = Database::default();
+		if path.is_dir() || is_image(path, data) {
+			let images = if path.is_dir() { images(path)? } else { vec![path.to_path_buf()] };
+			for image in images {
+				let uris = qr::decode(&image)?;
+				if uris.is_empty() {
+					eprintln!("Warning: no QR code found in {}", image.display());
+				}
+				for uri in uris {
+					let added = match uri::parse_into(&mut db, uri.trim()) {
+						Ok(added) => added,
+						Err(e) => {
+							eprintln!("Warning: skipping QR code in {}: {e:#}", image.display());
+							continue;
+						}
+					};
+					for entry in &db.entries[db.entries.len() - added..] {
+						eprintln!("{}: {}:{}", image.display(), entry.issuer(), entry.label());
+					}
+				}
+			}
+		} else {
+			let file = std::str::from_utf8(data).with_context(|| format!("{} is not a text file", path.display()))?;
+			for (n, line) in file.lines().enumerate() {
+				if line.trim().is_empty() {
+					continue;
+				}
+				uri::parse_into(&mut db, line.trim()).with_context(|| format!("Line {}", n + 1))?;
+			}
+		}
+		Ok(db)
+	}
+}
+
+struct AegisVault;
+
+impl Importer for AegisVault {
+	fn name(&self) -> &'static str {
+		"aegis"
+	}
+	fn description(&self) -> &'static str {
+		"Aegis vault JSON, encrypted or plain"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json"]
+	}
+	fn needs_password(&self, data: &[u8]) -> bool {
+		matches!(serde_json::from_slice(data), Ok(Aegis::Encrypted(_)))
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		json(data).is_some_and(|json| (json.get("header").is_some() && json.get("db").is_some()) || serde_json::from_value::<Aegis>(json).is_ok())
+	}
+	fn import(&self, _path: &Path, data: &[u8], options: &Options) -> Result<Database> {
+		let mut vault: Aegis = serde_json::from_slice(data).context("Not an Aegis vault")?;
+		if let Aegis::Encrypted(_) = vault {
+			vault.decrypt(password(options).context("The Aegis vault needs a password")?)?;
+		}
+		vault.into_database()
+	}
+}
+
+impl Exporter for AegisVault {
+	fn name(&self) -> &'static str {
+		"aegis"
+	}
+	fn description(&self) -> &'static str {
+		"Encrypted Aegis vault JSON"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json"]
+	}
+	fn needs_password(&self) -> bool {
+		true
+	}
+	fn export(&self, db: Database, options: &Options) -> Result<Vec<u8>> {
+		let mut vault = Aegis::from(db);
+		vault.encrypt(password(options).context("The Aegis vault needs a password")?)?;
+		let mut json = serde_json::to_vec_pretty(&vault)?;
+		json.push(b'\n');
+		Ok(json)
+	}
+}
+
+struct AndOtp;
+
+impl Importer for AndOtp {
+	fn name(&self) -> &'static str {
+		"andotp"
+	}
+	fn description(&self) -> &'static str {
+		"andOTP backup, encrypted or plain"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json", "bin"]
+	}
+	fn needs_password(&self, data: &[u8]) -> bool {
+		andotp::is_encrypted(data)
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		// Encrypted backups start with the PBKDF2 iteration count as big endian u32, then salt, nonce, data and tag
+		json(data).is_some_and(|json| json.is_array()) || (data.len() > 44 && data[0] == 0 && std::str::from_utf8(data).is_err())
+	}
+	fn import(&self, _path: &Path, data: &[u8], options: &Options) -> Result<Database> {
+		andotp::parse(data, password(options))
+	}
+}
+
+impl Exporter for AndOtp {
+	fn name(&self) -> &'static str {
+		"andotp"
+	}
+	fn description(&self) -> &'static str {
+		"andOTP backup, encrypted unless the password is empty"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json", "bin"]
+	}
+	fn needs_password(&self) -> bool {
+		true
+	}
+	fn export(&self, db: Database, options: &Options) -> Result<Vec<u8>> {
+		andotp::export(&db, password(options).filter(|password| !password.is_empty()))
+	}
+}
+
+struct TwoFas;
+
+impl Importer for TwoFas {
+	fn name(&self) -> &'static str {
+		"2fas"
+	}
+	fn description(&self) -> &'static str {
+		"2FAS backup, password protected or plain"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["2fas"]
+	}
+	fn needs_password(&self, data: &[u8]) -> bool {
+		twofas::is_encrypted(data)
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		json_with_key(data, &["services", "servicesEncrypted"])
+	}
+	fn import(&self, _path: &Path, data: &[u8], options: &Options) -> Result<Database> {
+		twofas::parse(data, password(options))
+	}
+}
+
+struct FreeOtpPlus;
+
+impl Importer for FreeOtpPlus {
+	fn name(&self) -> &'static str {
+		"freeotp-plus"
+	}
+	fn description(&self) -> &'static str {
+		"FreeOTP+ JSON export"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		json_with_key(data, &["tokens"])
+	}
+	fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		freeotp::parse_json(data)
+	}
+}
+
+struct FreeOtp;
+
+impl Importer for FreeOtp {
+	fn name(&self) -> &'static str {
+		"freeotp"
+	}
+	fn description(&self) -> &'static str {
+		"FreeOTP tokens.xml shared preferences file"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["xml"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		text(data).is_some_and(|text| text.starts_with('<') && text.contains("<map"))
+	}
+	fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		freeotp::parse_xml(data)
+	}
+}
+
+struct Bitwarden;
+
+impl Importer for Bitwarden {
+	fn name(&self) -> &'static str {
+		"bitwarden"
+	}
+	fn description(&self) -> &'static str {
+		"Unencrypted Bitwarden JSON export"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		json_with_key(data, &["items", "encrypted"])
+	}
+	fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		bitwarden::parse(data)
+	}
+}
+
+struct KeePassCsv;
+
+impl Importer for KeePassCsv {
+	fn name(&self) -> &'static str {
+		"keepass-csv"
+	}
+	fn description(&self) -> &'static str {
+		"KeePassXC CSV export"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["csv"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		let header = csv_header(data);
+		header.contains(&"Title") && header.contains(&"TOTP")
+	}
+	fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		keepass::parse_csv(data)
+	}
+}
+
+impl Exporter for KeePassCsv {
+	fn name(&self) -> &'static str {
+		"keepass-csv"
+	}
+	fn description(&self) -> &'static str {
+		"KeePassXC CSV, with the otpauth URI in the TOTP column"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["csv"]
+	}
+	fn export(&self, db: Database, _options: &Options) -> Result<Vec<u8>> {
+		Ok(keepass::export_csv(&db)?.into_bytes())
+	}
+}
+
+struct KeePassXml;
+
+impl Importer for KeePassXml {
+	fn name(&self) -> &'static str {
+		"keepass-xml"
+	}
+	fn description(&self) -> &'static str {
+		"Unencrypted KeePass 2 XML export"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["xml"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		text(data).is_some_and(|text| text.starts_with('<') && text.contains("<KeePassFile"))
+	}
+	fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		keepass::parse_xml(data)
+	}
+}
+
+impl Exporter for KeePassXml {
+	fn name(&self) -> &'static str {
+		"keepass-xml"
+	}
+	fn description(&self) -> &'static str {
+		"KeePass 2 XML, with the otpauth URI in the otp attribute"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["xml"]
+	}
+	fn export(&self, db: Database, _options: &Options) -> Result<Vec<u8>> {
+		Ok(keepass::export_xml(&db)?.into_bytes())
+	}
+}
+
+struct Pass;
+
+impl Importer for Pass {
+	fn name(&self) -> &'static str {
+		"pass"
+	}
+	fn description(&self) -> &'static str {
+		"Directory of decrypted password-store files with otpauth URI lines"
+	}
+	fn sniff(&self, path: &Path, _data: &[u8]) -> bool {
+		path.is_dir()
+	}
+	fn import(&self, path: &Path, _data: &[u8], _options: &Options) -> Result<Database> {
+		pass::parse(path)
+	}
+}
+
+impl Exporter for Pass {
+	fn name(&self) -> &'static str {
+		"pass"
+	}
+	fn description(&self) -> &'static str {
+		"Password-store tree of ISSUER/LABEL files with the otpauth URI, in --out-dir"
+	}
+	fn export(&self, db: Database, options: &Options) -> Result<Vec<u8>> {
+		pass::export(&db, options.out_dir.as_deref().context("--to pass needs --out-dir")?)?;
+		Ok(Vec::new())
+	}
+}
+
+struct Proton;
+
+impl Importer for Proton {
+	fn name(&self) -> &'static str {
+		"proton"
+	}
+	fn description(&self) -> &'static str {
+		"Plaintext Proton Authenticator JSON export"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["json"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		json_with_key(data, &["entries", "salt"])
+	}
+	fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+		proton::parse(data)
+	}
+}
+
+struct Csv;
+
+impl Importer for Csv {
+	fn name(&self) -> &'static str {
+		"csv"
+	}
+	fn description(&self) -> &'static str {
+		"CSV, with the columns of --columns or of the header row"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["csv"]
+	}
+	fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+		let header = csv_header(data);
+		header.iter().any(|field| field.parse::<csv::Column>().ok() == Some(csv::Column::Secret))
+			&& header.iter().all(|field| field.is_empty() || *field == "-" || field.parse::<csv::Column>().is_ok())
+	}
+	fn import(&self, _path: &Path, data: &[u8], options: &Options) -> Result<Database> {
+		let columns = options.setting("columns").map(csv::parse_columns).transpose()?;
+		csv::parse(data, columns.as_deref())
+	}
+}
+
+impl Exporter for Csv {
+	fn name(&self) -> &'static str {
+		"csv"
+	}
+	fn description(&self) -> &'static str {
+		"CSV, with the columns of --columns"
+	}
+	fn extensions(&self) -> &'static [&'static str] {
+		&["csv"]
+	}
+	fn export(&self, db: Database, options: &Options) -> Result<Vec<u8>> {
+		let columns = csv::parse_columns(options.setting("columns").unwrap_or(csv::DEFAULT_COLUMNS))?;
+		Ok(csv::export(&db, &columns)?.into_bytes())
+	}
+}
+
+struct Google;
+
+impl Exporter for Google {
+	fn name(&self) -> &'static str {
+		"google"
+	}
+	fn description(&self) -> &'static str {
+		"Google Authenticator migration URIs, or QR code images in --out-dir"
+	}
+	/// The settings are `batch-size` (entries per URI) and `format` (`png` or `svg` images)
+	fn export(&self, db: Database, options: &Options) -> Result<Vec<u8>> {
+		let batch_size = match options.setting("batch-size") {
+			Some(size) => size.parse().with_context(|| format!("Invalid batch-size {size}"))?,
+			None => migration::BATCH_SIZE,
+		};
+		let uris = migration::build(&db.entries, batch_size)?;
+		if uris.is_empty() {
+			anyhow::bail!("No entries that Google Authenticator supports");
+		}
+		let Some(dir) = &options.out_dir else {
+			return Ok(uris.iter().map(|uri| format!("{uri}\n")).collect::<String>().into_bytes());
+		};
+		let count = uris.len();
+		let names: Vec<String> = (1..=count).map(|n| format!("google-{n}-of-{count}")).collect();
+		for file in qr::write_images(names.iter().map(String::as_str).zip(uris.iter().map(String::as_str)), dir, options.setting("format").unwrap_or("png"))? {
+			eprintln!("Written {}", file.display());
+		}
+		Ok(Vec::new())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detect() {
+		let registry = Registry::default();
+		let detect = |data: &str| registry.detect(Path::new("input"), data.as_bytes()).map(|importer| importer.name());
+		assert_eq!(detect("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP\n\notpauth-migration://offline?data=x\n"), Some("uri"));
+		assert_eq!(detect(r#"[{"secret": "JBSWY3DPEHPK3PXP", "type": "TOTP"}]"#), Some("andotp"));
+		assert_eq!(detect(r#"{"services": [], "schemaVersion": 4}"#), Some("2fas"));
+		assert_eq!(detect(r#"{"encrypted": false, "items": []}"#), Some("bitwarden"));
+		assert_eq!(detect(r#"{"version": 1, "entries": []}"#), Some("proton"));
+		assert_eq!(detect("\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\",\"TOTP\"\n"), Some("keepass-csv"));
+		assert_eq!(detect("Issuer,Label,Secret\nGitHub,alice,JBSWY3DPEHPK3PXP\n"), Some("csv"));
+		assert_eq!(detect("<?xml version=\"1.0\"?>\n<KeePassFile><Root/></KeePassFile>"), Some("keepass-xml"));
+		assert_eq!(detect("hello, world\n"), None);
+		let vault = std::fs::read("test_databases/aegis_encrypted.json").unwrap();
+		let aegis = registry.detect(Path::new("vault.json"), &vault).unwrap();
+		assert_eq!(aegis.name(), "aegis");
+		assert!(aegis.needs_password(&vault));
+	}
+
+	#[test]
+	fn detect_image_without_extension() {
+		let registry = Registry::default();
+		let png = qr::png("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+		for name in ["codes", "export.dat"] {
+			assert_eq!(registry.detect(Path::new(name), &png).map(|importer| importer.name()), Some("uri"));
+		}
+		assert_eq!(registry.detect(Path::new("photo"), b"\xff\xd8\xff\xe0").map(|importer| importer.name()), Some("uri"));
+
+		let path = std::env::temp_dir().join(format!("aegisvault-format-codes-{}", std::process::id()));
+		std::fs::write(&path, &png).unwrap();
+		let db = Uri.import(&path, &png, &Options::default());
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(db.unwrap().entries.len(), 1);
+	}
+
+	#[test]
+	fn unrelated_qr_code() {
+		let dir = std::env::temp_dir().join(format!("aegisvault-format-qr-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(dir.join("a.png"), qr::png("https://example.com/welcome").unwrap()).unwrap();
+		std::fs::write(dir.join("b.png"), qr::png("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap()).unwrap();
+		let db = Uri.import(&dir, &[], &Options::default());
+		std::fs::remove_dir_all(&dir).unwrap();
+		let db = db.unwrap();
+		assert_eq!(db.entries.len(), 1);
+		assert_eq!(db.entries[0].label(), "alice");
+	}
+
+	#[test]
+	fn csv_columns_setting() {
+		let options = Options { settings: HashMap::from([("columns".to_string(), "label,secret".to_string())]), ..Default::default() };
+		let db = Csv.import(Path::new("input.csv"), b"alice,JBSWY3DPEHPK3PXP\n", &options).unwrap();
+		assert_eq!(db.entries[0].label(), "alice");
+		let csv = Csv.export(db, &options).unwrap();
+		assert_eq!(String::from_utf8(csv).unwrap().lines().next(), Some("label,secret"));
+	}
+
+	#[test]
+	fn google_export() {
+		let db = Uri
+			.import(
+				Path::new("uris.txt"),
+				b"otpauth://totp/a?secret=JBSWY3DPEHPK3PXP\notpauth://totp/b?secret=JBSWY3DPEHPK3PXP\n",
+				&Options::default(),
+			)
+			.unwrap();
+		let options = Options { settings: HashMap::from([("batch-size".to_string(), "1".to_string())]), ..Default::default() };
+		let text = String::from_utf8(Google.export(db, &options).unwrap()).unwrap();
+		let uris: Vec<&str> = text.lines().collect();
+		assert_eq!(uris.len(), 2);
+		assert_eq!(migration::parse(uris[1]).unwrap()[0].label(), "b");
+		assert!(Registry::default().exporter("google").is_some());
+	}
+
+	struct Lines;
+
+	impl Importer for Lines {
+		fn name(&self) -> &'static str {
+			"lines"
+		}
+		fn description(&self) -> &'static str {
+			"Base32 secrets, one per line"
+		}
+		fn sniff(&self, _path: &Path, data: &[u8]) -> bool {
+			text(data).is_some_and(|text| text.lines().all(|line| line.chars().all(|c| c.is_ascii_alphanumeric())))
+		}
+		fn import(&self, _path: &Path, data: &[u8], _options: &Options) -> Result<Database> {
+			let mut db = Database::default();
+			for line in text(data).unwrap_or_default().lines() {
+				let mut entry = crate::vault::Entry::default();
+				entry.info.set_secret(line)?;
+				entry.apply_defaults()?;
+				db.entries.push(entry);
+			}
+			Ok(db)
+		}
+	}
+
+	#[test]
+	fn custom_importer() {
+		let mut registry = Registry::default();
+		registry.add_importer(Lines);
+		assert_eq!(registry.importers().last().unwrap().name(), "lines");
+		let data = b"JBSWY3DPEHPK3PXP\nJBSWY3DPEHPK3PXQ\n";
+		let importer = registry.detect(Path::new("secrets"), data).unwrap();
+		assert_eq!(importer.name(), "lines");
+		let db = importer.import(Path::new("secrets"), data, &Options::default()).unwrap();
+		assert_eq!(db.entries.len(), 2);
+
+		let count = registry.importers().count();
+		registry.add_importer(Lines);
+		assert_eq!(registry.importers().count(), count);
+	}
+}
@@ -0,0 +1,174 @@
+//! FreeOTP+ and FreeOTP Import Module
+//!
+//! FreeOTP+ exports a JSON object with `tokens` and `tokenOrder`. FreeOTP keeps the same tokens in its
+//! Android shared preferences file `tokens.xml`: a `<string>` per token with the token JSON, named
+//! `issuer:label`, and one named `tokenOrder`. Secrets are arrays of signed bytes.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::algorithm::{Algorithm, Method};
+use crate::vault::{Database, Entry};
+
+const TOKEN_ORDER: &str = "tokenOrder";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+	tokens: Vec<Token>,
+	#[serde(default)]
+	token_order: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+	#[zeroize(skip)]
+	algo: Algorithm,
+	#[serde(default)]
+	#[zeroize(skip)]
+	counter: u32,
+	#[zeroize(skip)]
+	digits: u32,
+	#[serde(default)]
+	#[zeroize(skip)]
+	issuer_ext: String,
+	#[serde(default)]
+	#[zeroize(skip)]
+	label: String,
+	#[serde(default)]
+	#[zeroize(skip)]
+	period: Option<u32>,
+	secret: Vec<i8>,
+	#[serde(rename = "type")]
+	#[zeroize(skip)]
+	token_type: String,
+}
+
+impl Token {
+	/// The name FreeOTP uses for the token in `tokenOrder`
+	fn key(&self) -> String {
+		if self.issuer_ext.is_empty() { self.label.clone() } else { format!("{}:{}", self.issuer_ext, self.label) }
+	}
+}
+
+/// The shared preferences `<map>` of `tokens.xml`
+#[derive(Debug, Deserialize)]
+struct Preferences {
+	#[serde(rename = "string", default)]
+	strings: Vec<Preference>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Preference {
+	#[serde(rename = "@name")]
+	name: String,
+	#[serde(rename = "$text", default)]
+	value: String,
+}
+
+/// Read a FreeOTP+ JSON export
+pub fn parse_json(data: &[u8]) -> Result<Database> {
+	let export: Export = serde_json::from_slice(data).context("Not a FreeOTP+ export")?;
+	to_database(export.tokens, &export.token_order)
+}
+
+/// Read a FreeOTP `tokens.xml` shared preferences file
+pub fn parse_xml(data: &[u8]) -> Result<Database> {
+	let preferences: Preferences = quick_xml::de::from_reader(data).context("Not a FreeOTP tokens.xml file")?;
+	let mut order = Vec::new();
+	let mut tokens = Vec::new();
+	for preference in &preferences.strings {
+		let value = Zeroizing::new(preference.value.clone());
+		if preference.name == TOKEN_ORDER {
+			order = serde_json::from_str(&value).context("Invalid tokenOrder")?;
+		} else {
+			tokens.push(
+				serde_json::from_str(&value).with_context(|| format!("Invalid token {} (encrypted FreeOTP 2 backups are not supported)", preference.name))?,
+			);
+		}
+	}
+	to_database(tokens, &order)
+}
+
+/// Convert the tokens, in the order of `order` (tokens not in it come last)
+fn to_database(mut tokens: Vec<Token>, order: &[String]) -> Result<Database> {
+	tokens.sort_by_key(|token| order.iter().position(|key| *key == token.key()).unwrap_or(order.len()));
+	let mut db = Database::default();
+	for token in tokens {
+		let mut entry = Entry {
+			label: token.label.clone(),
+			issuer: Some(token.issuer_ext.clone()).filter(|issuer| !issuer.is_empty()),
+			..Default::default()
+		};
+		entry.method = match token.token_type.parse()? {
+			Method::TOTP if token.issuer_ext == "Steam" => Method::Steam,
+			method @ (Method::TOTP | Method::HOTP) => method,
+			_ => bail!("Unsupported FreeOTP token type {} for {}", token.token_type, token.key()),
+		};
+		entry.info.algorithm = token.algo;
+		entry.info.digits = token.digits;
+		if entry.method.is_event_based() {
+			entry.info.counter = Some(token.counter);
+		} else {
+			entry.info.period = token.period;
+		}
+		let secret = Zeroizing::new(token.secret.iter().map(|&byte| byte as u8).collect::<Vec<u8>>());
+		entry
+			.info
+			.set_secret(&data_encoding::BASE32_NOPAD.encode(&secret))
+			.with_context(|| format!("Invalid secret for {}", token.key()))?;
+		if entry.method == Method::Steam {
+			entry.info.digits = Method::Steam.default_digits();
+		}
+		entry.apply_defaults()?;
+		db.entries.push(entry);
+	}
+	Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The secret is "Hello!\xde\xad\xbe\xef" twice, as signed bytes
+	const SECRET: &str = "[72,101,108,108,111,33,-34,-83,-66,-17,72,101,108,108,111,33,-34,-83,-66,-17]";
+
+	#[test]
+	fn parse_plus() {
+		let export = format!(
+			r#"{{"tokenOrder":["Corp:bob","GitHub:alice"],"tokens":[
+				{{"algo":"SHA1","digits":6,"issuerExt":"GitHub","label":"alice","period":30,"secret":{SECRET},"type":"TOTP"}},
+				{{"algo":"SHA256","counter":4,"digits":8,"issuerExt":"Corp","label":"bob","period":30,"secret":{SECRET},"type":"HOTP"}},
+				{{"algo":"SHA1","digits":6,"issuerExt":"Steam","label":"carol","period":30,"secret":{SECRET},"type":"TOTP"}}
+			]}}"#
+		);
+		let db = parse_json(export.as_bytes()).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].label(), "bob");
+		assert_eq!(db.entries[0].method(), Method::HOTP);
+		assert_eq!(db.entries[0].counter(), Some(4));
+		assert_eq!(db.entries[0].period(), None);
+		assert_eq!(db.entries[1].issuer(), "GitHub");
+		assert_eq!(db.entries[1].secret(), "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP");
+		assert_eq!(db.entries[2].method(), Method::Steam);
+		assert_eq!(db.entries[2].digits(), Some(5));
+	}
+
+	#[test]
+	fn parse_tokens_xml() {
+		let token = format!(r#"{{"algo":"SHA1","counter":0,"digits":6,"issuerExt":"GitHub","label":"alice","period":30,"secret":{SECRET},"type":"TOTP"}}"#);
+		let xml = format!(
+			"<?xml version='1.0' encoding='utf-8' standalone='yes' ?>\n<map>\n<string name=\"GitHub:alice\">{}</string>\n<boolean name=\"firstRun\" value=\"false\" />\n<string name=\"tokenOrder\">[&quot;GitHub:alice&quot;]</string>\n</map>",
+			token.replace('"', "&quot;")
+		);
+		let db = parse_xml(xml.as_bytes()).unwrap();
+		assert_eq!(db.entries.len(), 1);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].label(), "alice");
+		assert_eq!(db.entries[0].secret(), "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP");
+
+		assert!(parse_xml(b"<map><string name=\"GitHub:alice\">encrypted</string></map>").is_err());
+	}
+}
@@ -0,0 +1,125 @@
+//! Entry Icon Module
+//!
+//! Aegis keeps the icon of an entry as base64 `icon`, with its `icon_mime` and the hex SHA-256 `icon_hash`
+//! of the image. SVG, PNG and JPEG images are supported.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::files::{UniqueNames, file_name};
+use crate::vault::Entry;
+
+pub const SVG: &str = "image/svg+xml";
+pub const PNG: &str = "image/png";
+pub const JPEG: &str = "image/jpeg";
+
+/// The MIME type of an SVG, PNG or JPEG image
+pub fn mime(image: &[u8]) -> Option<&'static str> {
+	if image.starts_with(b"\x89PNG") {
+		Some(PNG)
+	} else if image.starts_with(b"\xff\xd8\xff") {
+		Some(JPEG)
+	} else if std::str::from_utf8(image).is_ok_and(|text| text.trim_start().starts_with('<') && text.contains("<svg")) {
+		Some(SVG)
+	} else {
+		None
+	}
+}
+
+/// The file extension for an icon MIME type
+pub fn extension(mime: &str) -> Option<&'static str> {
+	match mime {
+		SVG => Some("svg"),
+		PNG => Some("png"),
+		JPEG => Some("jpg"),
+		_ => None,
+	}
+}
+
+/// Attach the icons in `dir` named after the issuer (like `github.svg`, ignoring case) to the entries without icon
+///
+/// Returns the number of entries which got an icon.
+pub fn attach_dir(entries: &mut [Entry], dir: &Path) -> Result<usize> {
+	let mut icons = HashMap::new();
+	for item in std::fs::read_dir(dir).with_context(|| format!("Cannot read directory {}", dir.display()))? {
+		let path = item?.path();
+		let is_icon = path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.is_some_and(|ext| ["svg", "png", "jpg", "jpeg"].contains(&ext.to_lowercase().as_str()));
+		if let (true, Some(stem)) = (is_icon, path.file_stem()) {
+			icons.insert(stem.to_string_lossy().to_lowercase(), path);
+		}
+	}
+	let mut count = 0;
+	for entry in entries.iter_mut().filter(|entry| entry.thumbnail.is_none()) {
+		let Some(path) = icons.get(&entry.issuer().to_lowercase()) else { continue };
+		let image = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+		entry.set_icon(&image).with_context(|| format!("Invalid icon {}", path.display()))?;
+		count += 1;
+	}
+	Ok(count)
+}
+
+/// Write the icon of every entry with one into `dir` as `ISSUER_LABEL.EXT`, returns the written files
+pub fn extract(entries: &[Entry], dir: &Path) -> Result<Vec<PathBuf>> {
+	std::fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+	let mut names = UniqueNames::default();
+	let mut files = Vec::new();
+	for entry in entries {
+		let Some(image) = entry.icon()? else { continue };
+		let ext = entry.icon_mime.as_deref().or_else(|| mime(&image)).and_then(extension).unwrap_or("bin");
+		let name = names.unique(&file_name(&format!("{}_{}", entry.issuer(), entry.label())));
+		let file = dir.join(format!("{name}.{ext}"));
+		std::fs::write(&file, &image).with_context(|| format!("Cannot write {}", file.display()))?;
+		files.push(file);
+	}
+	Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SVG_IMAGE: &[u8] = b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>";
+
+	#[test]
+	fn set_icon() {
+		assert_eq!(mime(b"\x89PNG\r\n\x1a\n"), Some(PNG));
+		assert_eq!(mime(b"\xff\xd8\xff\xe0"), Some(JPEG));
+		assert_eq!(mime(SVG_IMAGE), Some(SVG));
+		assert_eq!(mime(b"GIF89a"), None);
+
+		let mut entry = Entry::default();
+		entry.set_icon(SVG_IMAGE).unwrap();
+		assert_eq!(entry.icon_mime.as_deref(), Some(SVG));
+		assert_eq!(entry.icon().unwrap().as_deref(), Some(SVG_IMAGE));
+		assert_eq!(entry.icon_hash.as_deref(), Some(hex::encode(ring::digest::digest(&ring::digest::SHA256, SVG_IMAGE)).as_str()));
+		assert!(entry.set_icon(b"GIF89a").is_err());
+
+		entry.strip_icon();
+		assert_eq!((entry.thumbnail, entry.icon_mime, entry.icon_hash), (None, None, None));
+	}
+
+	#[test]
+	fn attach_and_extract() {
+		let dir = std::env::temp_dir().join(format!("aegisvault-icon-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(dir.join("GitHub.svg"), SVG_IMAGE).unwrap();
+
+		let mut entries = vec![
+			Entry { issuer: Some("github".to_string()), label: "alice".to_string(), ..Default::default() },
+			Entry { issuer: Some("Corp".to_string()), label: "bob".to_string(), ..Default::default() },
+		];
+		assert_eq!(attach_dir(&mut entries, &dir).unwrap(), 1);
+		assert_eq!(entries[0].icon_mime.as_deref(), Some(SVG));
+		assert!(entries[1].thumbnail.is_none());
+
+		let files = extract(&entries, &dir.join("out")).unwrap();
+		assert_eq!(files, vec![dir.join("out/github_alice.svg")]);
+		assert_eq!(std::fs::read(&files[0]).unwrap(), SVG_IMAGE);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
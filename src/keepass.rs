@@ -0,0 +1,312 @@
+//! KeePass/KeePassXC Import/Export Module
+//!
+//! KeePassXC keeps TOTP in the `otp` attribute of an entry (an otpauth URI), older versions in the
+//! `TOTP Seed` and `TOTP Settings` (`PERIOD;DIGITS`, `S` as digits for Steam) attributes.
+//! Supported are the KeePassXC CSV export and the unencrypted KeePass 2 XML export,
+//! entries are written with their otpauth URI in the `otp` attribute (the `TOTP` column for CSV).
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::uri;
+use crate::vault::{Database, Entry};
+
+const ROOT: &str = "Root";
+const CSV_HEADER: [&str; 7] = ["Group", "Title", "Username", "Password", "URL", "Notes", "TOTP"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeePassFile {
+	#[serde(rename = "Root")]
+	root: Root,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Root {
+	#[serde(rename = "Group", default)]
+	groups: Vec<Group>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Group {
+	#[serde(rename = "Name", default)]
+	name: String,
+	#[serde(rename = "Entry", default)]
+	entries: Vec<KeePassEntry>,
+	#[serde(rename = "Group", default)]
+	groups: Vec<Group>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeePassEntry {
+	#[serde(rename = "UUID", default, skip_serializing_if = "String::is_empty")]
+	uuid: String,
+	#[serde(rename = "String", default)]
+	strings: Vec<Field>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Field {
+	#[serde(rename = "Key")]
+	key: String,
+	#[serde(rename = "Value", default)]
+	value: Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Value {
+	#[serde(rename = "@ProtectInMemory", default, skip_serializing_if = "Option::is_none")]
+	protect_in_memory: Option<String>,
+	#[serde(rename = "$text", default)]
+	text: String,
+}
+
+impl KeePassEntry {
+	fn get(&self, key: &str) -> Option<&str> {
+		self.strings
+			.iter()
+			.find(|field| field.key == key)
+			.map(|field| field.value.text.as_str())
+			.filter(|text| !text.is_empty())
+	}
+
+	fn push(&mut self, key: &str, text: String, protect: bool) {
+		self.strings
+			.push(Field { key: key.to_string(), value: Value { protect_in_memory: protect.then(|| "True".to_string()), text } });
+	}
+}
+
+/// Read the TOTP entries of an unencrypted KeePass 2 XML export, entries without TOTP are skipped
+///
+/// The title becomes the issuer and the username the label (when set), the name of the KeePass group becomes a group.
+pub fn parse_xml(data: &[u8]) -> Result<Database> {
+	let file: KeePassFile = quick_xml::de::from_reader(data).context("Not a KeePass XML export")?;
+	let mut db = Database::default();
+	// The top group is the root of the database, not a real group
+	for root in &file.root.groups {
+		add_group(&mut db, root, None)?;
+	}
+	Ok(db)
+}
+
+fn add_group(db: &mut Database, group: &Group, name: Option<&str>) -> Result<()> {
+	for item in &group.entries {
+		let title = item.get("Title").unwrap_or_default();
+		let totp = match (item.get("otp"), item.get("TOTP Seed")) {
+			(Some(otp), _) => Zeroizing::new(otp.to_string()),
+			(None, Some(seed)) => Zeroizing::new(legacy_uri(seed, item.get("TOTP Settings"))),
+			(None, None) => continue,
+		};
+		let mut entry = totp_entry(&totp, title, item.get("UserName").unwrap_or_default())?;
+		entry.note = item.get("Notes").unwrap_or_default().to_string();
+		if let Some(name) = name {
+			entry.groups.push(db.group_uuid(name));
+		}
+		db.entries.push(entry);
+	}
+	for subgroup in &group.groups {
+		add_group(db, subgroup, Some(&subgroup.name))?;
+	}
+	Ok(())
+}
+
+/// Build the otpauth URI for the legacy `TOTP Seed` and `TOTP Settings` attributes
+fn legacy_uri(seed: &str, settings: Option<&str>) -> String {
+	let seed = urlencoding::encode(seed);
+	let mut settings = settings.unwrap_or("30;6").split(';');
+	let period = settings.next().unwrap_or("30");
+	let uri = match settings.next().unwrap_or("6") {
+		"S" => format!("otpauth://steam/?secret={seed}&period={period}"),
+		digits => format!("otpauth://totp/?secret={seed}&period={period}&digits={digits}"),
+	};
+	match settings.next() {
+		Some(algorithm) => format!("{uri}&algorithm={algorithm}"),
+		None => uri,
+	}
+}
+
+/// The entry for a TOTP value: an otpauth URI, or a bare base32 secret
+fn totp_entry(totp: &str, title: &str, username: &str) -> Result<Entry> {
+	let mut entry = if totp.starts_with("otpauth://") {
+		uri::parse(totp).with_context(|| format!("Invalid TOTP for {title}"))?
+	} else {
+		let mut entry = Entry::default();
+		entry.info.set_secret(totp).with_context(|| format!("Invalid TOTP secret for {title}"))?;
+		entry.apply_defaults()?;
+		entry
+	};
+	if !title.is_empty() {
+		entry.issuer = Some(title.to_string());
+	}
+	if !username.is_empty() {
+		entry.label = username.to_string();
+	}
+	Ok(entry)
+}
+
+/// Read the TOTP entries of a KeePassXC CSV export, rows without TOTP are skipped
+pub fn parse_csv(data: &[u8]) -> Result<Database> {
+	let mut reader = csv::Reader::from_reader(data);
+	let headers = reader.headers().context("Not a KeePassXC CSV export")?.clone();
+	let column = |name: &str| headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+	let totp_column = column("TOTP").context("No TOTP column in the KeePassXC CSV export")?;
+	let (group_column, title_column, username_column, notes_column) = (column("Group"), column("Title"), column("Username"), column("Notes"));
+
+	let mut db = Database::default();
+	for record in reader.records() {
+		let record = record?;
+		let field = |column: Option<usize>| column.and_then(|column| record.get(column)).unwrap_or_default();
+		let totp = Zeroizing::new(field(Some(totp_column)).trim().to_string());
+		if totp.is_empty() {
+			continue;
+		}
+		let mut entry = totp_entry(&totp, field(title_column), field(username_column))?;
+		entry.note = field(notes_column).to_string();
+		// Groups are paths from the root group: Root/Work
+		if let Some((_, name)) = field(group_column).rsplit_once('/') {
+			entry.groups.push(db.group_uuid(name));
+		}
+		db.entries.push(entry);
+	}
+	Ok(db)
+}
+
+/// Write the entries as a KeePass 2 XML file with the otpauth URI in the `otp` attribute
+///
+/// Entries are put in a KeePass group named after their first group.
+pub fn export_xml(db: &Database) -> Result<String> {
+	let mut root = Group { name: ROOT.to_string(), ..Default::default() };
+	for entry in &db.entries {
+		let item = keepass_entry(entry)?;
+		match db.group_names(entry).first() {
+			Some(name) => match root.groups.iter_mut().find(|group| &group.name == name) {
+				Some(group) => group.entries.push(item),
+				None => root.groups.push(Group { name: name.clone(), entries: vec![item], ..Default::default() }),
+			},
+			None => root.entries.push(item),
+		}
+	}
+	let file = KeePassFile { root: Root { groups: vec![root] } };
+	let mut xml = String::new();
+	let mut serializer = quick_xml::se::Serializer::with_root(&mut xml, Some("KeePassFile"))?;
+	serializer.indent('\t', 1);
+	file.serialize(serializer)?;
+	Ok(format!("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n{xml}\n"))
+}
+
+fn keepass_entry(entry: &Entry) -> Result<KeePassEntry> {
+	let uuid = uuid::Uuid::parse_str(&entry.uuid).unwrap_or_else(|_| uuid::Uuid::new_v4());
+	let mut item = KeePassEntry { uuid: data_encoding::BASE64.encode(uuid.as_bytes()), ..Default::default() };
+	item.push("Title", entry.issuer(), false);
+	item.push("UserName", entry.label(), false);
+	item.push("Password", String::new(), true);
+	item.push("Notes", entry.note.clone(), false);
+	item.push("otp", uri::build(entry)?, true);
+	Ok(item)
+}
+
+/// Write the entries as a KeePassXC CSV file with the otpauth URI in the `TOTP` column
+pub fn export_csv(db: &Database) -> Result<String> {
+	let mut writer = csv::WriterBuilder::new().quote_style(csv::QuoteStyle::Always).from_writer(Vec::new());
+	writer.write_record(CSV_HEADER)?;
+	for entry in &db.entries {
+		let group = match db.group_names(entry).first() {
+			Some(name) => format!("{ROOT}/{name}"),
+			None => ROOT.to_string(),
+		};
+		writer.write_record([group, entry.issuer(), entry.label(), String::new(), String::new(), entry.note.clone(), uri::build(entry)?])?;
+	}
+	match String::from_utf8(writer.into_inner()?) {
+		Ok(csv) => Ok(csv),
+		Err(_) => bail!("CSV is not valid UTF-8"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::algorithm::{Algorithm, Method};
+
+	const XML: &str = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+	<Meta><Generator>KeePass</Generator></Meta>
+	<Root>
+		<Group>
+			<Name>Database</Name>
+			<Entry>
+				<String><Key>Title</Key><Value>GitHub</Value></String>
+				<String><Key>UserName</Key><Value>alice</Value></String>
+				<String><Key>otp</Key><Value ProtectInMemory="True">otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&amp;algorithm=SHA256</Value></String>
+				<History><Entry><String><Key>Title</Key><Value>Old</Value></String></Entry></History>
+			</Entry>
+			<Group>
+				<Name>Work</Name>
+				<Entry>
+					<String><Key>Title</Key><Value>Steam</Value></String>
+					<String><Key>UserName</Key><Value>carol</Value></String>
+					<String><Key>TOTP Seed</Key><Value>JRZCL47CMXVOQMNPZR2F7J4RGI</Value></String>
+					<String><Key>TOTP Settings</Key><Value>30;S</Value></String>
+					<String><Key>Notes</Key><Value>Family account</Value></String>
+				</Entry>
+				<Entry>
+					<String><Key>Title</Key><Value>Mail</Value></String>
+					<String><Key>Password</Key><Value ProtectInMemory="True">hunter2</Value></String>
+				</Entry>
+			</Group>
+			<Entry>
+				<String><Key>Title</Key><Value>Corp</Value></String>
+				<String><Key>UserName</Key><Value>bob</Value></String>
+				<String><Key>TOTP Seed</Key><Value>JBSWY3DPEHPK3PXP</Value></String>
+				<String><Key>TOTP Settings</Key><Value>60;8</Value></String>
+			</Entry>
+		</Group>
+	</Root>
+</KeePassFile>"#;
+
+	#[test]
+	fn xml_round_trip() {
+		let db = parse_xml(XML.as_bytes()).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].algorithm(), Algorithm::SHA256);
+		assert!(db.entries[0].groups.is_empty());
+		assert_eq!(db.entries[1].issuer(), "Corp");
+		assert_eq!(db.entries[1].label(), "bob");
+		assert_eq!(db.entries[1].period(), Some(60));
+		assert_eq!(db.entries[1].digits(), Some(8));
+		assert_eq!(db.entries[2].method(), Method::Steam);
+		assert_eq!(db.entries[2].digits(), Some(5));
+		assert_eq!(db.entries[2].note, "Family account");
+		assert_eq!(db.group_names(&db.entries[2]), vec!["Work"]);
+
+		let xml = export_xml(&db).unwrap();
+		let restored = parse_xml(xml.as_bytes()).unwrap();
+		assert_eq!(restored.entries.len(), 3);
+		assert_eq!(restored.entries[1].secret(), "JBSWY3DPEHPK3PXP");
+		assert_eq!(restored.entries[1].period(), Some(60));
+		assert_eq!(restored.group_names(&restored.entries[2]), vec!["Work"]);
+	}
+
+	#[test]
+	fn csv_round_trip() {
+		let csv = "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\",\"TOTP\",\"Icon\"\n\
+			\"Root/Work\",\"GitHub\",\"alice\",\"pw\",\"\",\"Line 1\nLine 2\",\"otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&digits=8\",\"0\"\n\
+			\"Root\",\"Mail\",\"bob\",\"pw\",\"\",\"\",\"\",\"0\"\n\
+			\"Root\",\"Corp\",\"carol\",\"pw\",\"\",\"\",\"JBSWY3DPEHPK3PXP\",\"0\"\n";
+		let db = parse_csv(csv.as_bytes()).unwrap();
+		assert_eq!(db.entries.len(), 2);
+		assert_eq!(db.entries[0].digits(), Some(8));
+		assert_eq!(db.entries[0].note, "Line 1\nLine 2");
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Work"]);
+		assert_eq!(db.entries[1].issuer(), "Corp");
+		assert!(db.entries[1].groups.is_empty());
+
+		let restored = parse_csv(export_csv(&db).unwrap().as_bytes()).unwrap();
+		assert_eq!(restored.entries.len(), 2);
+		assert_eq!(restored.entries[0].note, "Line 1\nLine 2");
+		assert_eq!(restored.entries[1].label(), "carol");
+		assert_eq!(restored.group_names(&restored.entries[0]), vec!["Work"]);
+
+		assert!(parse_csv(b"Title,Username\nGitHub,alice\n").is_err());
+	}
+}
@@ -1,4 +1,20 @@
 #![doc = include_str!("../README.md")]
 
 pub mod algorithm;
+pub mod andotp;
+pub mod bitwarden;
+pub mod cli;
+pub mod csv;
+pub mod files;
+pub mod format;
+pub mod freeotp;
+pub mod icon;
+pub mod keepass;
+pub mod migration;
+pub mod otp;
+pub mod pass;
+pub mod proton;
+pub mod qr;
+pub mod twofas;
+pub mod uri;
 pub mod vault;
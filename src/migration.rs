@@ -0,0 +1,210 @@
+//! Google Authenticator Migration Import/Export Module
+//!
+//! Google Authenticator exports and imports accounts as `otpauth-migration://offline?data=DATA` URIs (shown as QR codes),
+//! where `DATA` is a base64 encoded protobuf `MigrationPayload`, see:
+//! <https://github.com/qistoph/otp_export/blob/master/OtpMigration.proto>
+
+use anyhow::{Context, Result, bail};
+use prost::Message;
+use rand::Rng;
+use url::Url;
+
+use crate::algorithm::{Algorithm, Method};
+use crate::uri::{self, split_label};
+use crate::vault::Entry;
+
+#[derive(Clone, PartialEq, Message)]
+struct MigrationPayload {
+	#[prost(message, repeated, tag = "1")]
+	otp_parameters: Vec<OtpParameters>,
+	#[prost(int32, tag = "2")]
+	version: i32,
+	#[prost(int32, tag = "3")]
+	batch_size: i32,
+	#[prost(int32, tag = "4")]
+	batch_index: i32,
+	#[prost(int32, tag = "5")]
+	batch_id: i32,
+}
+
+// The enums of the payload are kept as their integer values
+#[derive(Clone, PartialEq, Message)]
+struct OtpParameters {
+	#[prost(bytes = "vec", tag = "1")]
+	secret: Vec<u8>,
+	#[prost(string, tag = "2")]
+	name: String,
+	#[prost(string, tag = "3")]
+	issuer: String,
+	#[prost(int32, tag = "4")]
+	algorithm: i32,
+	#[prost(int32, tag = "5")]
+	digits: i32,
+	#[prost(int32, tag = "6")]
+	otp_type: i32,
+	#[prost(int64, tag = "7")]
+	counter: i64,
+}
+
+/// Entries per migration URI that keep the QR code easy to scan
+pub const BATCH_SIZE: usize = 10;
+
+/// Pack entries into otpauth-migration URIs of at most `batch_size` entries each
+///
+/// Entries that Google Authenticator cannot represent (Steam, mOTP and Yandex entries,
+/// periods other than 30 and digits other than 6 or 8) are skipped with a warning.
+pub fn build<'a>(entries: impl IntoIterator<Item = &'a Entry>, batch_size: usize) -> Result<Vec<String>> {
+	if batch_size == 0 {
+		bail!("Batch size must be at least 1");
+	}
+	let mut otps = Vec::new();
+	for entry in entries {
+		let name = format!("{}:{}", entry.issuer(), entry.label());
+		let otp_type = match entry.method {
+			Method::HOTP => 1,
+			Method::TOTP if entry.info.period == Some(30) => 2,
+			Method::TOTP => {
+				eprintln!("Skipping {name}: Google Authenticator only supports a period of 30");
+				continue;
+			}
+			method => {
+				eprintln!("Skipping {name}: Google Authenticator does not support {} entries", method.display_name());
+				continue;
+			}
+		};
+		let digits = match entry.info.digits {
+			6 => 1,
+			8 => 2,
+			n => {
+				eprintln!("Skipping {name}: Google Authenticator does not support {n} digits");
+				continue;
+			}
+		};
+		let algorithm = match entry.info.algorithm {
+			Algorithm::SHA1 => 1,
+			Algorithm::SHA256 => 2,
+			Algorithm::SHA512 => 3,
+			Algorithm::MD5 => 4,
+		};
+		otps.push(OtpParameters {
+			secret: entry.info.secret_bytes().with_context(|| format!("Invalid secret for {name}"))?.to_vec(),
+			name: if entry.issuer().is_empty() { entry.label().to_string() } else { name },
+			issuer: entry.issuer().to_string(),
+			algorithm,
+			digits,
+			otp_type,
+			counter: i64::from(entry.info.counter.unwrap_or(0)),
+		});
+	}
+
+	let batches: Vec<Vec<OtpParameters>> = otps.chunks(batch_size).map(<[OtpParameters]>::to_vec).collect();
+	let batch_id = rand::rng().random_range(1..i32::MAX);
+	let batch_count = i32::try_from(batches.len())?;
+	let mut uris = Vec::with_capacity(batches.len());
+	for (index, otp_parameters) in batches.into_iter().enumerate() {
+		let payload = MigrationPayload { otp_parameters, version: 1, batch_size: batch_count, batch_index: i32::try_from(index)?, batch_id };
+		let data = data_encoding::BASE64.encode(&payload.encode_to_vec());
+		uris.push(format!("otpauth-migration://offline?data={}", urlencoding::encode(&data)));
+	}
+	Ok(uris)
+}
+
+/// Parse an otpauth-migration URI into its entries
+pub fn parse(line: &str) -> Result<Vec<Entry>> {
+	let uri = Url::parse(line)?;
+	if uri.scheme() != "otpauth-migration" {
+		bail!("Expected scheme otpauth-migration, found {}", uri.scheme());
+	}
+	let (_, data) = uri.query_pairs().find(|(key, _)| key == "data").context("Missing data")?;
+	let data = uri::decode_base64(&data).context("Data is not valid base64")?;
+	let payload = MigrationPayload::decode(data.as_slice()).context("Data is not a migration payload")?;
+
+	let mut entries = Vec::new();
+	for otp in payload.otp_parameters {
+		let (label_issuer, label) = split_label(&otp.name);
+		let mut entry = Entry { label: label.to_string(), ..Default::default() };
+		entry.issuer = match otp.issuer.as_str() {
+			"" => label_issuer.map(ToOwned::to_owned),
+			issuer => Some(issuer.to_string()),
+		};
+		entry.method = match otp.otp_type {
+			1 => Method::HOTP,
+			0 | 2 => Method::TOTP,
+			n => bail!("Unknown OTP type {n} for {}", otp.name),
+		};
+		entry.info.algorithm = match otp.algorithm {
+			0 | 1 => Algorithm::SHA1,
+			2 => Algorithm::SHA256,
+			3 => Algorithm::SHA512,
+			4 => Algorithm::MD5,
+			n => bail!("Unknown algorithm {n} for {}", otp.name),
+		};
+		entry.info.digits = match otp.digits {
+			0 | 1 => 6,
+			2 => 8,
+			n => bail!("Unknown digit count {n} for {}", otp.name),
+		};
+		if entry.method == Method::HOTP {
+			entry.info.counter = Some(u32::try_from(otp.counter).with_context(|| format!("Invalid counter for {}", otp.name))?);
+		}
+		entry
+			.info
+			.set_secret(&data_encoding::BASE32_NOPAD.encode(&otp.secret))
+			.with_context(|| format!("Invalid secret for {}", otp.name))?;
+		entry.apply_defaults()?;
+		entries.push(entry);
+	}
+	Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_example() {
+		let entries = parse("otpauth-migration://offline?data=CjEKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhhbXBsZTAC").unwrap();
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].label(), "alice@google.com");
+		assert_eq!(entries[0].issuer(), "Example");
+		assert_eq!(entries[0].secret(), "JBSWY3DPEHPK3PXP");
+		assert_eq!(entries[0].method(), Method::TOTP);
+		assert_eq!(entries[0].digits(), Some(6));
+		assert_eq!(entries[0].period(), Some(30));
+
+		assert!(parse("otpauth-migration://offline?data=!!!").is_err());
+		assert!(parse("otpauth-migration://offline").is_err());
+	}
+
+	#[test]
+	fn build_batches() {
+		let uri = "otpauth-migration://offline?data=CjEKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhhbXBsZTAC";
+		let mut entries: Vec<Entry> = (0..3).flat_map(|_| parse(uri).unwrap()).collect();
+		entries[1].method = Method::HOTP;
+		entries[1].info.counter = Some(7);
+		entries[1].info.period = None;
+		entries[2].method = Method::Steam;
+
+		let uris = build(&entries, 1).unwrap();
+		assert_eq!(uris.len(), 2);
+		let payloads: Vec<MigrationPayload> = uris
+			.iter()
+			.map(|uri| {
+				let data = urlencoding::decode(uri.strip_prefix("otpauth-migration://offline?data=").unwrap()).unwrap();
+				MigrationPayload::decode(data_encoding::BASE64.decode(data.as_bytes()).unwrap().as_slice()).unwrap()
+			})
+			.collect();
+		assert_eq!((payloads[0].batch_size, payloads[0].batch_index), (2, 0));
+		assert_eq!((payloads[1].batch_size, payloads[1].batch_index), (2, 1));
+		assert_eq!(payloads[0].batch_id, payloads[1].batch_id);
+
+		let parsed: Vec<Entry> = uris.iter().flat_map(|uri| parse(uri).unwrap()).collect();
+		assert_eq!(parsed[0].issuer(), "Example");
+		assert_eq!(parsed[0].label(), "alice@google.com");
+		assert_eq!(parsed[0].secret(), "JBSWY3DPEHPK3PXP");
+		assert_eq!(parsed[1].method(), Method::HOTP);
+		assert_eq!(parsed[1].info.counter, Some(7));
+
+		assert!(build(&entries, 0).is_err());
+	}
+}
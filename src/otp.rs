@@ -0,0 +1,293 @@
+//! OTP Code Generation Module
+//!
+//! HOTP: <https://www.rfc-editor.org/rfc/rfc4226>
+//! TOTP: <https://www.rfc-editor.org/rfc/rfc6238>
+//!
+//! Steam, mOTP and Yandex codes are ported from Aegis:
+//! <https://github.com/beemdevelopment/Aegis/tree/master/app/src/main/java/com/beemdevelopment/aegis/crypto/otp>
+//!
+//! All methods are expressed in terms of a counter: the stored counter for HOTP,
+//! the number of periods since the unix epoch for the time-based methods.
+
+use anyhow::{Context, Result};
+use md5::{Digest, Md5};
+use ring::{digest, hmac};
+use subtle::ConstantTimeEq;
+
+use crate::algorithm::Method;
+use crate::vault::{Detail, Entry};
+
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+
+/// The largest window of [`verify_code`], a wider one would make checking a way to guess codes
+pub const MAX_WINDOW: u64 = 100;
+
+/// Compute the current code of an entry at the given unix time
+pub fn generate(entry: &Entry, time: u64) -> Result<String> {
+	code(entry, counter_at(entry, time)?)
+}
+
+/// The counter of an entry at the given unix time
+pub fn counter_at(entry: &Entry, time: u64) -> Result<u64> {
+	if entry.method.is_event_based() {
+		Ok(u64::from(entry.info.counter.unwrap_or(0)))
+	} else {
+		Ok(time / period(&entry.info)?)
+	}
+}
+
+/// Seconds until the code of a time-based entry changes
+pub fn remaining(entry: &Entry, time: u64) -> Option<u64> {
+	let period = period(&entry.info).ok().filter(|_| entry.method.is_time_based())?;
+	Some(period - time % period)
+}
+
+/// Compute the code of an entry for the given counter
+pub fn code(entry: &Entry, counter: u64) -> Result<String> {
+	let info = &entry.info;
+	match entry.method {
+		Method::TOTP | Method::HOTP => {
+			info.check_digits(entry.method)?;
+			let code = truncate(&hmac_hash(info, counter)?) % 10u64.pow(info.digits);
+			Ok(format!("{code:0width$}", width = info.digits as usize))
+		}
+		Method::Steam => steam(info, counter),
+		Method::Motp => motp(info, counter),
+		Method::Yandex => yandex(info, counter),
+	}
+}
+
+/// Check a code against an entry, comparing in constant time
+///
+/// Time-based entries accept the codes of up to `window` periods before and after `time`,
+/// HOTP entries the codes of the stored counter and up to `window` counters after it.
+/// Returns the counter of the matching code, for HOTP the stored counter should become one more
+/// than that to resynchronise. Fails when `window` is above [`MAX_WINDOW`].
+pub fn verify_code(entry: &Entry, code: &str, time: u64, window: u64) -> Result<Option<u64>> {
+	if window > MAX_WINDOW {
+		anyhow::bail!("Window {window} is too large, at most {MAX_WINDOW}");
+	}
+	let current = counter_at(entry, time)?;
+	let counters = if entry.method.is_event_based() {
+		current..=current.saturating_add(window)
+	} else {
+		current.saturating_sub(window)..=current.saturating_add(window)
+	};
+	let code: String = code.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+	let mut found = None;
+	// Check all counters, so the time taken does not depend on which one matches
+	for counter in counters {
+		let expected = self::code(entry, counter)?.to_lowercase();
+		if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) && found.is_none() {
+			found = Some(counter);
+		}
+	}
+	Ok(found)
+}
+
+fn period(info: &Detail) -> Result<u64> {
+	match info.period {
+		Some(0) | None => anyhow::bail!("Missing period"),
+		Some(period) => Ok(u64::from(period)),
+	}
+}
+
+fn hmac_hash(info: &Detail, counter: u64) -> Result<hmac::Tag> {
+	let key = hmac::Key::new(info.algorithm.try_into()?, &info.secret_bytes()?);
+	Ok(hmac::sign(&key, &counter.to_be_bytes()))
+}
+
+/// Dynamic truncation of RFC 4226 section 5.3
+fn truncate(hash: &hmac::Tag) -> u64 {
+	let hash = hash.as_ref();
+	let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+	u64::from(u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff)
+}
+
+/// Compute a Steam Guard code from its own alphabet
+pub fn steam(detail: &Detail, counter: u64) -> Result<String> {
+	detail.check_digits(Method::Steam)?;
+	let mut code = truncate(&hmac_hash(detail, counter)?) as usize;
+	let mut chars = String::with_capacity(detail.digits as usize);
+	for _ in 0..detail.digits {
+		chars.push(STEAM_ALPHABET[code % STEAM_ALPHABET.len()] as char);
+		code /= STEAM_ALPHABET.len();
+	}
+	Ok(chars)
+}
+
+/// Compute an mOTP code: the start of the MD5 hash of counter, hex secret and pin
+pub fn motp(detail: &Detail, counter: u64) -> Result<String> {
+	detail.check_digits(Method::Motp)?;
+	let pin = detail.pin.as_deref().context("mOTP entries need a pin")?;
+	let secret = hex::encode(&*detail.secret_bytes()?);
+	let hash = hex::encode(Md5::digest(format!("{counter}{secret}{pin}")));
+	Ok(hash[..detail.digits as usize].to_string())
+}
+
+/// Compute a Yandex code of lowercase latin letters
+pub fn yandex(detail: &Detail, counter: u64) -> Result<String> {
+	detail.check_digits(Method::Yandex)?;
+	let pin = detail.pin.as_deref().context("Yandex entries need a pin")?;
+	let secret = detail.secret_bytes()?;
+
+	// The HMAC key is the SHA256 hash of the pin followed by the secret
+	let key_hash = digest::digest(&digest::SHA256, &[pin.as_bytes(), &secret].concat());
+	let key_hash = match key_hash.as_ref() {
+		[0, rest @ ..] => rest,
+		all => all,
+	};
+	let key = hmac::Key::new(hmac::HMAC_SHA256, key_hash);
+	let hash = hmac::sign(&key, &counter.to_be_bytes());
+	let hash = hash.as_ref();
+	let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+	let mut code = u64::from_be_bytes(hash[offset..offset + 8].try_into()?) & 0x7fff_ffff_ffff_ffff;
+
+	// Base 26 with letters instead of digits
+	code %= 26u64.pow(detail.digits);
+	let mut chars = vec![b'a'; detail.digits as usize];
+	for c in chars.iter_mut().rev() {
+		*c += (code % 26) as u8;
+		code /= 26;
+	}
+	Ok(String::from_utf8(chars)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::algorithm::Algorithm;
+
+	fn entry(method: Method, algorithm: Algorithm, seed: &[u8], digits: u32) -> Entry {
+		let mut entry = Entry { method, ..Default::default() };
+		entry.info.secret = data_encoding::BASE32_NOPAD.encode(seed);
+		entry.info.algorithm = algorithm;
+		entry.info.digits = digits;
+		entry.apply_defaults().unwrap();
+		entry
+	}
+
+	#[test]
+	fn rfc4226_vectors() {
+		// RFC 4226 appendix D
+		let codes = ["755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489"];
+		let mut entry = entry(Method::HOTP, Algorithm::SHA1, b"12345678901234567890", 6);
+		for (counter, expected) in codes.into_iter().enumerate() {
+			assert_eq!(code(&entry, counter as u64).unwrap(), expected);
+			entry.info.counter = Some(counter as u32);
+			assert_eq!(generate(&entry, 0).unwrap(), expected);
+		}
+	}
+
+	#[test]
+	fn rfc6238_vectors() {
+		// RFC 6238 appendix B, with the seed length matching the hash
+		let sha1 = entry(Method::TOTP, Algorithm::SHA1, b"12345678901234567890", 8);
+		let sha256 = entry(Method::TOTP, Algorithm::SHA256, b"12345678901234567890123456789012", 8);
+		let sha512 = entry(Method::TOTP, Algorithm::SHA512, b"1234567890123456789012345678901234567890123456789012345678901234", 8);
+		let vectors = [
+			(59, "94287082", "46119246", "90693936"),
+			(1111111109, "07081804", "68084774", "25091201"),
+			(1111111111, "14050471", "67062674", "99943326"),
+			(1234567890, "89005924", "91819424", "93441116"),
+			(2000000000, "69279037", "90698825", "38618901"),
+			(20000000000, "65353130", "77737706", "47863826"),
+		];
+		for (time, code_sha1, code_sha256, code_sha512) in vectors {
+			assert_eq!(generate(&sha1, time).unwrap(), code_sha1);
+			assert_eq!(generate(&sha256, time).unwrap(), code_sha256);
+			assert_eq!(generate(&sha512, time).unwrap(), code_sha512);
+		}
+	}
+
+	#[test]
+	fn steam_vectors() {
+		// Cross-checked with an independent implementation of the Steam Guard algorithm
+		let mut entry = entry(Method::Steam, Algorithm::SHA1, b"", 5);
+		entry.info.secret = "JRZCL47CMXVOQMNPZR2F7J4RGI".to_string();
+		let vectors = [(0, "C2F47"), (1000000000, "DHCXD"), (1600000000, "87GYP"), (2000000000, "G7879")];
+		for (time, expected) in vectors {
+			assert_eq!(generate(&entry, time).unwrap(), expected);
+		}
+	}
+
+	#[test]
+	fn motp_vectors() {
+		// From Aegis' MOTPTest
+		let mut entry = entry(Method::Motp, Algorithm::MD5, &[], 6);
+		entry.info.set_hex_secret("e3152afee62599c8").unwrap();
+		entry.info.pin = Some("1234".to_string());
+		assert_eq!(generate(&entry, 165892298).unwrap(), "e7d8b6");
+		assert_eq!(generate(&entry, 123456789).unwrap(), "4ebfb2");
+	}
+
+	#[test]
+	fn yandex_vectors() {
+		// From Aegis' YAOTPTest
+		let vectors = [
+			("5239", "6SB2IKNM6OBZPAVBVTOHDKS4FAAAAAAADFUTQMBTRY", 1641559648, "umozdicq"),
+			("7586", "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI", 1581064020, "oactmacq"),
+			("7586", "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI", 1581090810, "wemdwrix"),
+			("5210481216086702", "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSG4HXU3M", 1581091469, "dfrpywob"),
+			("5210481216086702", "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSG4HXU3M", 1581093059, "vunyprpd"),
+		];
+		for (pin, secret, time, code) in vectors {
+			let mut entry = Entry { method: Method::Yandex, ..Default::default() };
+			entry.info.pin = Some(pin.to_string());
+			entry.info.set_yandex_secret(secret).unwrap();
+			entry.apply_defaults().unwrap();
+			assert_eq!(generate(&entry, time).unwrap(), code);
+		}
+	}
+
+	#[test]
+	fn verify() {
+		let mut entry = entry(Method::TOTP, Algorithm::SHA1, b"12345678901234567890", 8);
+		assert_eq!(generate(&entry, 59).unwrap(), "94287082");
+		assert_eq!(verify_code(&entry, "9428 7082", 59, 0).unwrap(), Some(1));
+		assert_eq!(verify_code(&entry, "94287082", 89, 1).unwrap(), Some(1));
+		assert_eq!(verify_code(&entry, "94287082", 119, 1).unwrap(), None);
+		assert_eq!(verify_code(&entry, "12345678", 59, 1).unwrap(), None);
+
+		entry.method = Method::HOTP;
+		entry.info.digits = 6;
+		entry.info.period = None;
+		entry.info.counter = Some(3);
+		// RFC 4226 appendix D, counter 5
+		assert_eq!(verify_code(&entry, "254676", 0, 1).unwrap(), None);
+		assert_eq!(verify_code(&entry, "254676", 0, 2).unwrap(), Some(5));
+		assert_eq!(verify_code(&entry, "359152", 0, 2).unwrap(), None);
+		assert!(verify_code(&entry, "359152", 0, MAX_WINDOW).is_ok());
+		assert!(verify_code(&entry, "359152", 0, u64::MAX).is_err());
+	}
+
+	#[test]
+	fn remaining_time() {
+		let mut entry = Entry::default();
+		entry.apply_defaults().unwrap();
+		assert_eq!(remaining(&entry, 59), Some(1));
+		assert_eq!(remaining(&entry, 60), Some(30));
+
+		entry.method = Method::HOTP;
+		assert_eq!(remaining(&entry, 60), None);
+	}
+
+	#[test]
+	fn invalid_digits() {
+		// Vaults read from disk skip apply_defaults, so out of range digits must give an error instead of a panic
+		let mut entry = entry(Method::TOTP, Algorithm::SHA1, b"12345678901234567890", 6);
+		entry.info.pin = Some("1234".to_string());
+		for (method, digits) in [(Method::TOTP, 20), (Method::HOTP, 0), (Method::Steam, 11), (Method::Motp, 33), (Method::Yandex, 14)] {
+			entry.method = method;
+			entry.info.digits = digits;
+			assert!(generate(&entry, 59).is_err());
+		}
+
+		entry.method = Method::TOTP;
+		entry.info.digits = 11;
+		assert!(entry.apply_defaults().is_err());
+		entry.info.digits = 0;
+		entry.apply_defaults().unwrap();
+		assert_eq!(entry.info.digits, 6);
+	}
+}
@@ -0,0 +1,149 @@
+//! pass (password-store) Import/Export Module
+//!
+//! With the pass-otp extension the (decrypted) content of a pass file contains an otpauth URI line.
+//! Entries are kept as `ISSUER/LABEL` files, entries without issuer as `LABEL` at the top.
+//! Import walks a directory of already decrypted files, the metadata of pass and git (like `.gpg-id` or `.git`) is skipped.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::files::{self, UniqueNames};
+use crate::uri;
+use crate::vault::{Database, Entry};
+
+/// Files and directories of pass and git which never hold entries
+const METADATA: &[&str] = &[".git", ".gitattributes", ".gpg-id", ".extensions"];
+
+/// Read the entries of all files with an otpauth URI line under `dir`, in path order
+///
+/// When the URI has no issuer (or label), the name of the parent directory (or of the file) is used.
+pub fn parse(dir: &Path) -> Result<Database> {
+	let mut files = Vec::new();
+	walk(dir, &mut files)?;
+	files.sort();
+
+	let mut db = Database::default();
+	for file in files {
+		let data = std::fs::read(&file).with_context(|| format!("Cannot read {}", file.display()))?;
+		let Ok(content) = String::from_utf8(data).map(Zeroizing::new) else {
+			eprintln!("Skipping {}: not a text file (pass files need to be decrypted)", file.display());
+			continue;
+		};
+		let Some(line) = content.lines().map(str::trim).find(|line| line.starts_with("otpauth://")) else {
+			continue;
+		};
+		let mut entry = uri::parse(line).with_context(|| format!("Invalid otpauth URI in {}", file.display()))?;
+		let relative = file.strip_prefix(dir).unwrap_or(&file);
+		if entry.label.is_empty() {
+			let name = relative.file_name().unwrap_or_default().to_string_lossy();
+			entry.label = name.strip_suffix(".txt").or_else(|| name.strip_suffix(".gpg")).unwrap_or(&name).to_string();
+		}
+		if entry.issuer.is_none() {
+			entry.issuer = relative.parent().and_then(Path::file_name).map(|issuer| issuer.to_string_lossy().to_string());
+		}
+		db.entries.push(entry);
+	}
+	Ok(db)
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+	for item in std::fs::read_dir(dir).with_context(|| format!("Cannot read directory {}", dir.display()))? {
+		let path = item?.path();
+		if path.file_name().is_some_and(|name| METADATA.iter().any(|metadata| name == *metadata)) {
+			continue;
+		}
+		if path.is_dir() {
+			walk(&path, files)?;
+		} else {
+			files.push(path);
+		}
+	}
+	Ok(())
+}
+
+/// Write one `ISSUER/LABEL` file per entry under `dir` with its otpauth URI
+///
+/// `/` in names becomes `_`, duplicate names get a `-N` suffix. Existing files are not overwritten.
+pub fn export(db: &Database, dir: &Path) -> Result<()> {
+	let mut paths = UniqueNames::default();
+	for entry in &db.entries {
+		let path = PathBuf::from(paths.unique(&entry_path(dir, entry).to_string_lossy()));
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent).with_context(|| format!("Cannot create {}", parent.display()))?;
+		}
+		let mut file = files::create(&path).with_context(|| format!("Cannot create {}", path.display()))?;
+		writeln!(file, "{}", Zeroizing::new(uri::build(entry)?).as_str())?;
+		eprintln!("Written {}", path.display());
+	}
+	Ok(())
+}
+
+fn entry_path(dir: &Path, entry: &Entry) -> PathBuf {
+	let name = |name: &str| match name.replace('/', "_").trim() {
+		"" | "." | ".." => "_".to_string(),
+		name => name.to_string(),
+	};
+	match entry.issuer.as_deref().filter(|issuer| !issuer.is_empty()) {
+		Some(issuer) => dir.join(name(issuer)).join(name(&entry.label)),
+		None => dir.join(name(&entry.label)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::algorithm::Method;
+
+	#[test]
+	fn round_trip() {
+		let dir = std::env::temp_dir().join(format!("aegisvault-pass-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(dir.join("GitHub")).unwrap();
+		std::fs::create_dir_all(dir.join(".git")).unwrap();
+		std::fs::write(dir.join("GitHub/alice@example.com"), "hunter2\notpauth://totp/?secret=JBSWY3DPEHPK3PXP\n").unwrap();
+		std::fs::write(dir.join("bob.txt"), "otpauth://hotp/Corp:bob?secret=JBSWY3DPEHPK3PXP&counter=3\n").unwrap();
+		std::fs::write(dir.join("notes"), "no otp here\n").unwrap();
+		std::fs::write(dir.join(".git/config"), "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP\n").unwrap();
+		std::fs::write(dir.join(".gpg-id"), "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP\n").unwrap();
+
+		let db = parse(&dir).unwrap();
+		assert_eq!(db.entries.len(), 2);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].label(), "alice@example.com");
+		assert_eq!(db.entries[1].issuer(), "Corp");
+		assert_eq!(db.entries[1].label(), "bob");
+		assert_eq!(db.entries[1].method(), Method::HOTP);
+
+		let out = dir.join("out");
+		export(&db, &out).unwrap();
+		assert!(out.join("GitHub/alice@example.com").is_file());
+		assert!(out.join("Corp/bob").is_file());
+		assert!(export(&db, &out).is_err());
+
+		let restored = parse(&out).unwrap();
+		assert_eq!(restored.entries.len(), 2);
+		assert_eq!(restored.entries[0].label(), "bob");
+		assert_eq!(restored.entries[0].counter(), Some(3));
+		assert_eq!(restored.entries[1].secret(), "JBSWY3DPEHPK3PXP");
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn leading_dot() {
+		let dir = std::env::temp_dir().join(format!("aegisvault-pass-dot-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		let mut db = Database::default();
+		db.entries.push(uri::parse("otpauth://totp/.corp:.alice?secret=JBSWY3DPEHPK3PXP&issuer=.corp").unwrap());
+		db.entries.push(uri::parse("otpauth://totp/.bob?secret=JBSWY3DPEHPK3PXP").unwrap());
+		export(&db, &dir).unwrap();
+
+		let restored = parse(&dir).unwrap();
+		assert_eq!(restored.entries.len(), 2);
+		assert_eq!(restored.entries[0].label(), ".bob");
+		assert_eq!(restored.entries[1].issuer(), ".corp");
+		assert_eq!(restored.entries[1].label(), ".alice");
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,92 @@
+//! Proton Authenticator Import Module
+//!
+//! The plaintext JSON export has a list of `entries`, each keeps its token as `content.uri`: an otpauth
+//! URI, or `steam://SECRET` for Steam. Password protected exports are not supported.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::algorithm::Method;
+use crate::uri;
+use crate::vault::{Database, Entry};
+
+#[derive(Debug, Deserialize)]
+struct Export {
+	#[serde(default)]
+	salt: Option<String>,
+	#[serde(default)]
+	entries: Vec<ProtonEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtonEntry {
+	content: Content,
+	#[serde(default)]
+	note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct Content {
+	#[serde(default)]
+	#[zeroize(skip)]
+	name: String,
+	uri: String,
+}
+
+/// Read the entries of a plaintext Proton Authenticator JSON export
+///
+/// The entry name is used as issuer when the URI has none.
+pub fn parse(data: &[u8]) -> Result<Database> {
+	let export: Export = serde_json::from_slice(data).context("Not a Proton Authenticator export")?;
+	if export.salt.is_some() {
+		bail!("Password protected Proton Authenticator exports are not supported, export without password");
+	}
+
+	let mut db = Database::default();
+	for item in &export.entries {
+		let content = &item.content;
+		let mut entry = if let Some(secret) = content.uri.strip_prefix("steam://") {
+			let mut entry = Entry { method: Method::Steam, label: content.name.clone(), ..Default::default() };
+			entry.info.set_secret(secret).with_context(|| format!("Invalid Steam secret for {}", content.name))?;
+			entry
+		} else {
+			uri::parse(&content.uri).with_context(|| format!("Invalid URI for {}", content.name))?
+		};
+		if entry.issuer.is_none() && !content.name.is_empty() && content.name != entry.label {
+			entry.issuer = Some(content.name.clone());
+		}
+		entry.note = item.note.clone().unwrap_or_default();
+		entry.apply_defaults()?;
+		db.entries.push(entry);
+	}
+	Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::algorithm::Algorithm;
+
+	#[test]
+	fn parse_export() {
+		let export = r#"{"version": 1, "entries": [
+			{"id": "1", "content": {"id": "1", "name": "GitHub", "uri": "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub", "entry_type": "Totp"}, "note": "Work"},
+			{"id": "2", "content": {"id": "2", "name": "Corp", "uri": "otpauth://totp/bob?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&digits=8", "entry_type": "Totp"}, "note": null},
+			{"id": "3", "content": {"id": "3", "name": "Steam", "uri": "steam://JRZCL47CMXVOQMNPZR2F7J4RGI", "entry_type": "Steam"}}
+		]}"#;
+		let db = parse(export.as_bytes()).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].label(), "alice");
+		assert_eq!(db.entries[0].note, "Work");
+		assert_eq!(db.entries[1].issuer(), "Corp");
+		assert_eq!(db.entries[1].label(), "bob");
+		assert_eq!(db.entries[1].algorithm(), Algorithm::SHA256);
+		assert_eq!(db.entries[2].method(), Method::Steam);
+		assert_eq!(db.entries[2].digits(), Some(5));
+
+		assert!(parse(br#"{"version": 1, "salt": "c2FsdA==", "content": "..."}"#).is_err());
+		assert!(parse(br#"{"entries": [{"content": {"name": "Bad", "uri": "https://example.com"}}]}"#).is_err());
+	}
+}
@@ -0,0 +1,98 @@
+//! QR Code Module
+//!
+//! Renders text (an otpauth URI) as a QR code in the terminal, as PNG or as SVG,
+//! writes them as image files and decodes the QR codes in PNG and JPEG images.
+
+use anyhow::{Context, Result, bail};
+use image::{ImageFormat, ImageReader, Luma};
+use qrcode::QrCode;
+use qrcode::render::{svg, unicode};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::files::{self, UniqueNames};
+
+/// Minimum size in pixels of PNG and SVG images
+const MIN_SIZE: u32 = 256;
+
+/// Unicode half-block rendering, light on dark so it scans on a dark terminal
+pub fn terminal(data: &str) -> Result<String> {
+	let code = QrCode::new(data).context("Cannot encode QR code")?;
+	Ok(code.render::<unicode::Dense1x2>().dark_color(unicode::Dense1x2::Light).light_color(unicode::Dense1x2::Dark).build())
+}
+
+/// A PNG image
+pub fn png(data: &str) -> Result<Vec<u8>> {
+	let code = QrCode::new(data).context("Cannot encode QR code")?;
+	let image = code.render::<Luma<u8>>().min_dimensions(MIN_SIZE, MIN_SIZE).build();
+	let mut png = Cursor::new(Vec::new());
+	image.write_to(&mut png, ImageFormat::Png).context("Cannot encode PNG image")?;
+	Ok(png.into_inner())
+}
+
+/// An SVG image
+pub fn svg(data: &str) -> Result<String> {
+	let code = QrCode::new(data).context("Cannot encode QR code")?;
+	Ok(code.render::<svg::Color>().min_dimensions(MIN_SIZE, MIN_SIZE).build())
+}
+
+/// Write QR codes of `(name, data)` into `dir` as `png` or `svg` images, returns the written files
+///
+/// The images may hold secrets, so they are only readable by the user. Names already taken get a `-N` suffix.
+pub fn write_images<'a>(codes: impl IntoIterator<Item = (&'a str, &'a str)>, dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+	std::fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+	let mut names = UniqueNames::default();
+	let mut written = Vec::new();
+	for (name, data) in codes {
+		let image = match extension {
+			"png" => png(data)?,
+			"svg" => svg(data)?.into_bytes(),
+			_ => bail!("Unknown image format {extension}, expected png or svg"),
+		};
+		let file = dir.join(format!("{}.{extension}", names.unique(name)));
+		files::write(&file, &image).with_context(|| format!("Cannot write {}", file.display()))?;
+		written.push(file);
+	}
+	Ok(written)
+}
+
+/// The contents of all QR codes found in an image, QR codes that cannot be decoded are skipped with a warning
+pub fn decode(path: &Path) -> Result<Vec<String>> {
+	let image = ImageReader::open(path)?
+		.with_guessed_format()?
+		.decode()
+		.with_context(|| format!("Cannot read image {}", path.display()))?;
+	let mut prepared = rqrr::PreparedImage::prepare(image.to_luma8());
+	let mut contents = Vec::new();
+	for grid in prepared.detect_grids() {
+		match grid.decode() {
+			Ok((_, content)) => contents.push(content),
+			Err(e) => eprintln!("Warning: cannot decode a QR code in {}: {e}", path.display()),
+		}
+	}
+	Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn render() {
+		let uri = "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP";
+		let text = terminal(uri).unwrap();
+		assert!(text.lines().count() > 10);
+		assert!(text.contains('█') || text.contains('▀') || text.contains('▄'));
+		assert!(svg(uri).unwrap().starts_with("<?xml"));
+	}
+
+	#[test]
+	fn png_round_trip() {
+		let uri = "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub";
+		let path = std::env::temp_dir().join(format!("aegisvault-qr-{}.png", std::process::id()));
+		std::fs::write(&path, png(uri).unwrap()).unwrap();
+		let decoded = decode(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(decoded.unwrap(), vec![uri.to_string()]);
+	}
+}
@@ -0,0 +1,192 @@
+//! 2FAS Import Module
+//!
+//! 2FAS backups (`.2fas`) are JSON with a list of `services` and `groups`. Password protected backups
+//! leave `services` empty and keep them in `servicesEncrypted` as `DATA:SALT:IV` (base64), where DATA is
+//! the AES-GCM ciphertext and tag of the services JSON and the key is derived with PBKDF2-HMAC-SHA256.
+
+use aes_gcm::{KeyInit, aead::Aead};
+use anyhow::{Context, Result, anyhow, bail};
+use ring::pbkdf2;
+use serde::Deserialize;
+use std::num::NonZeroU32;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::algorithm::{Algorithm, Method};
+use crate::vault::{Database, Entry};
+
+const ITERATIONS: u32 = 10_000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+	#[serde(default)]
+	services: Vec<Service>,
+	services_encrypted: Option<String>,
+	#[serde(default)]
+	groups: Vec<TwoFasGroup>,
+}
+
+#[derive(Debug, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(rename_all = "camelCase")]
+struct Service {
+	#[zeroize(skip)]
+	name: String,
+	secret: String,
+	#[zeroize(skip)]
+	otp: Otp,
+	#[serde(default)]
+	#[zeroize(skip)]
+	group_id: Option<String>,
+	#[serde(default)]
+	#[zeroize(skip)]
+	order: Option<Order>,
+	#[serde(default)]
+	#[zeroize(skip)]
+	notes: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Otp {
+	#[serde(default)]
+	account: Option<String>,
+	#[serde(default)]
+	label: Option<String>,
+	#[serde(default)]
+	issuer: Option<String>,
+	#[serde(default)]
+	digits: Option<u32>,
+	#[serde(default)]
+	period: Option<u32>,
+	#[serde(default)]
+	algorithm: Option<Algorithm>,
+	#[serde(default)]
+	counter: Option<u32>,
+	#[serde(default)]
+	token_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Order {
+	position: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct TwoFasGroup {
+	id: String,
+	name: String,
+}
+
+/// Whether the data is a password protected 2FAS backup
+pub fn is_encrypted(data: &[u8]) -> bool {
+	serde_json::from_slice::<Backup>(data).is_ok_and(|backup| backup.services_encrypted.is_some())
+}
+
+/// Read a 2FAS backup, decrypting it with the password when it is protected
+pub fn parse(data: &[u8], password: Option<&str>) -> Result<Database> {
+	let backup: Backup = serde_json::from_slice(data).context("Not a 2FAS backup")?;
+	let mut services = match &backup.services_encrypted {
+		Some(encrypted) => {
+			let json = decrypt(encrypted, password.context("The 2FAS backup is encrypted, a password is needed")?)?;
+			serde_json::from_slice(&json).context("Cannot read the decrypted 2FAS services")?
+		}
+		None => backup.services,
+	};
+	// Keep the order of the app
+	services.sort_by_key(|service| service.order.as_ref().map(|order| order.position));
+
+	let mut db = Database::default();
+	for service in services {
+		let otp = &service.otp;
+		let label = otp.account.clone().or_else(|| otp.label.clone()).unwrap_or_default();
+		let issuer = Some(service.name.clone()).filter(|name| !name.is_empty()).or_else(|| otp.issuer.clone());
+		let mut entry = Entry { label, issuer, note: service.notes.clone().unwrap_or_default(), ..Default::default() };
+		entry.method = match otp.token_type.as_deref().unwrap_or("TOTP").parse()? {
+			method @ (Method::TOTP | Method::HOTP | Method::Steam) => method,
+			_ => bail!("Unsupported 2FAS token type for {}", service.name),
+		};
+		entry.info.algorithm = otp.algorithm.unwrap_or(entry.method.default_algorithm());
+		entry.info.digits = otp.digits.unwrap_or(0);
+		if entry.method.is_event_based() {
+			entry.info.counter = Some(otp.counter.unwrap_or(0));
+		} else {
+			entry.info.period = otp.period;
+		}
+		entry.info.set_secret(&service.secret).with_context(|| format!("Invalid secret for {}", service.name))?;
+		if let Some(group) = service.group_id.as_ref().and_then(|id| backup.groups.iter().find(|group| &group.id == id)) {
+			entry.groups.push(db.group_uuid(&group.name));
+		}
+		entry.apply_defaults()?;
+		db.entries.push(entry);
+	}
+	Ok(db)
+}
+
+fn decrypt(encrypted: &str, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+	let parts = encrypted
+		.split(':')
+		.map(|part| data_encoding::BASE64.decode(part.as_bytes()))
+		.collect::<Result<Vec<_>, _>>()
+		.context("servicesEncrypted is not valid base64")?;
+	let [data, salt, iv] = parts.as_slice() else {
+		bail!("servicesEncrypted should have 3 parts");
+	};
+	let mut key = Zeroizing::new([0u8; 32]);
+	pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, NonZeroU32::new(ITERATIONS).unwrap(), salt, password.as_bytes(), key.as_mut());
+	let cipher = aes_gcm::Aes256Gcm::new_from_slice(key.as_ref()).map_err(|_| anyhow!("Could not create cipher from key"))?;
+	if iv.len() != 12 {
+		bail!("Invalid IV length {}", iv.len());
+	}
+	let plaintext = cipher
+		.decrypt(aes_gcm::Nonce::from_slice(iv), data.as_ref())
+		.map_err(|_| anyhow!("Cannot decrypt the 2FAS backup. Wrong password?"))?;
+	Ok(Zeroizing::new(plaintext))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SERVICES: &str = r#"[
+		{"name":"Steam","secret":"JRZCL47CMXVOQMNPZR2F7J4RGI","otp":{"account":"carol","digits":5,"period":30,"algorithm":"SHA1","tokenType":"STEAM"},"order":{"position":1}},
+		{"name":"GitHub","secret":"JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP","otp":{"account":"alice","issuer":"GitHub","digits":6,"period":30,"algorithm":"SHA1","tokenType":"TOTP"},"order":{"position":0},"groupId":"g1","notes":"Recovery codes in the safe"},
+		{"name":"Corp","secret":"JBSWY3DPEHPK3PXP","otp":{"label":"bob","digits":8,"algorithm":"SHA256","counter":3,"tokenType":"HOTP"},"order":{"position":2}}
+	]"#;
+
+	#[test]
+	fn parse_plain() {
+		let backup = format!(r#"{{"services":{SERVICES},"groups":[{{"id":"g1","name":"Work","isExpanded":true}}],"schemaVersion":4}}"#);
+		assert!(!is_encrypted(backup.as_bytes()));
+		let db = parse(backup.as_bytes(), None).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].label(), "alice");
+		assert_eq!(db.entries[0].note, "Recovery codes in the safe");
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Work"]);
+		assert_eq!(db.entries[1].method(), Method::Steam);
+		assert_eq!(db.entries[1].digits(), Some(5));
+		assert_eq!(db.entries[2].method(), Method::HOTP);
+		assert_eq!(db.entries[2].counter(), Some(3));
+		assert_eq!(db.entries[2].algorithm(), Algorithm::SHA256);
+		assert!(db.entries[2].groups.is_empty());
+	}
+
+	#[test]
+	fn parse_encrypted() {
+		let salt = b"0123456789abcdef";
+		let iv = b"0123456789ab";
+		let mut key = [0u8; 32];
+		pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, NonZeroU32::new(ITERATIONS).unwrap(), salt, b"secret", &mut key);
+		let cipher = aes_gcm::Aes256Gcm::new_from_slice(&key).unwrap();
+		let data = cipher.encrypt(aes_gcm::Nonce::from_slice(iv), SERVICES.as_bytes()).unwrap();
+		let encrypted = [data.as_slice(), salt, iv].map(|part| data_encoding::BASE64.encode(part)).join(":");
+		let backup = format!(r#"{{"services":[],"servicesEncrypted":"{encrypted}","groups":[],"schemaVersion":4}}"#);
+
+		assert!(is_encrypted(backup.as_bytes()));
+		assert!(parse(backup.as_bytes(), None).is_err());
+		assert!(parse(backup.as_bytes(), Some("wrong")).is_err());
+		let db = parse(backup.as_bytes(), Some("secret")).unwrap();
+		assert_eq!(db.entries.len(), 3);
+		assert_eq!(db.entries[0].secret(), "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP");
+	}
+}
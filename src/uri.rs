@@ -0,0 +1,348 @@
+//! otpauth URI Import/Export Module
+//!
+//! Description of the Key URI format:
+//! <https://github.com/google/google-authenticator/wiki/Key-Uri-Format>
+//! <https://www.ietf.org/archive/id/draft-linuxgemini-otpauth-uri-02.html>
+//!
+//! The label is either `accountname` or `issuer:accountname`. The colon can be literal or
+//! percent-encoded as `%3A`, both are treated the same. When both the label prefix and the
+//! `issuer` parameter are given they should be equal, if not the parameter wins.
+//!
+//! Ente Auth adds a `codeDisplay` parameter with JSON display settings, its note, tags (as groups)
+//! and pin state (as favorite) are kept, entries in its trash are skipped. A `group` parameter (which can be
+//! repeated) adds the entry to a group.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use url::Url;
+use urlencoding::{decode, encode};
+
+use crate::algorithm::{Algorithm, Method};
+use crate::migration;
+use crate::vault::{Database, Entry};
+
+/// The `codeDisplay` parameter of Ente Auth
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CodeDisplay {
+	pinned: bool,
+	trashed: bool,
+	tags: Vec<String>,
+	note: String,
+}
+
+/// Parse an otpauth or otpauth-migration URI into its entries
+pub fn parse_entries(line: &str) -> Result<Vec<Entry>> {
+	if line.starts_with("otpauth-migration:") { migration::parse(line) } else { Ok(vec![parse(line)?]) }
+}
+
+/// Parse an otpauth or otpauth-migration URI into the entries of `db`, returns the number of entries added
+///
+/// Unlike [`parse_entries`] this keeps the `group` parameters and the tags of Ente Auth as groups, and skips trashed entries.
+pub fn parse_into(db: &mut Database, line: &str) -> Result<usize> {
+	if line.starts_with("otpauth-migration:") {
+		let entries = migration::parse(line)?;
+		let count = entries.len();
+		db.entries.extend(entries);
+		return Ok(count);
+	}
+	let (mut entry, display) = parse_uri(line)?;
+	if display.trashed {
+		eprintln!("Skipping '{}': in the trash", entry.label);
+		return Ok(0);
+	}
+	entry.groups = display.tags.iter().map(|tag| db.group_uuid(tag)).collect();
+	db.entries.push(entry);
+	Ok(1)
+}
+
+/// Parse a single otpauth URI into an Entry
+pub fn parse(line: &str) -> Result<Entry> {
+	parse_uri(line).map(|(entry, _)| entry)
+}
+
+fn parse_uri(line: &str) -> Result<(Entry, CodeDisplay)> {
+	let mut otp = Entry::default();
+	let mut display = CodeDisplay::default();
+	let mut groups = Vec::new();
+	let uri = Url::parse(line)?;
+	if uri.scheme() != "otpauth" {
+		bail!("Expected scheme otpauth, found {}", uri.scheme());
+	}
+	let method = uri.host_str().context("Missing otpauth URI type")?;
+	otp.method = match method {
+		"totp" => Method::TOTP,
+		"hotp" => Method::HOTP,
+		"steam" => Method::Steam,
+		"motp" => Method::Motp,
+		"yandex" => Method::Yandex,
+		_ => bail!("Unknown otpauth URI type: {method}"),
+	};
+	let path = decode(uri.path().trim_start_matches('/')).context("Label is not valid UTF-8")?;
+	let (label_issuer, label) = split_label(&path);
+	otp.label = label.to_string();
+	let mut issuer = None;
+	let mut encoder = None;
+	for (key, val) in uri.query_pairs() {
+		match key.as_ref() {
+			// mOTP secrets are hexadecimal
+			"secret" if otp.method == Method::Motp => otp.info.set_hex_secret(&val).context("Invalid secret")?,
+			"secret" if otp.method == Method::Yandex => otp.info.set_yandex_secret(&val).context("Invalid secret")?,
+			"secret" => otp.info.set_secret(&val).context("Invalid secret")?,
+			"algorithm" => otp.info.algorithm = val.parse::<Algorithm>().with_context(|| format!("Unknown algorithm: {val}"))?,
+			"digits" => otp.info.digits = val.parse::<u32>().with_context(|| format!("Invalid digits: {val}"))?,
+			"period" => otp.info.period = Some(val.parse::<u32>().with_context(|| format!("Invalid period: {val}"))?),
+			"counter" => otp.info.counter = Some(val.parse::<u32>().with_context(|| format!("Invalid counter: {val}"))?),
+			// Query pairs are already percent-decoded
+			"issuer" => issuer = Some(val.to_string()),
+			// Vendor extensions
+			"pin" => otp.info.pin = Some(val.to_string()),
+			"encoder" => encoder = Some(val.to_string()),
+			"image" => set_image(&mut otp, &val),
+			"group" => groups.push(val.to_string()),
+			"codeDisplay" => display = serde_json::from_str(&val).context("Invalid codeDisplay")?,
+			"color" | "lock" => (),
+			_ => eprintln!("Warning: ignoring unknown key '{key}' for '{}'", otp.label),
+		};
+	}
+	if otp.info.secret.is_empty() {
+		bail!("Missing secret");
+	}
+	if otp.method == Method::Yandex && otp.info.pin.is_none() {
+		bail!("Missing pin for Yandex entry");
+	}
+	otp.issuer = match (label_issuer, issuer) {
+		(Some(prefix), Some(param)) => {
+			if prefix != param {
+				eprintln!("Warning: issuer '{prefix}' in label of '{}' differs from issuer parameter '{param}', using '{param}'", otp.label);
+			}
+			Some(param)
+		}
+		(prefix, param) => param.or(prefix.map(ToOwned::to_owned)),
+	};
+	// Steam Guard codes are exported as totp with `encoder=steam` and/or issuer Steam
+	let steam_issuer = otp.issuer.as_deref().is_some_and(|i| i.eq_ignore_ascii_case("steam"));
+	if encoder.as_deref().is_some_and(|e| e.eq_ignore_ascii_case("steam")) || (otp.method == Method::TOTP && steam_issuer) {
+		otp.method = Method::Steam;
+		otp.info.digits = 5;
+	}
+	display.tags.extend(groups);
+	otp.note.clone_from(&display.note);
+	otp.favorite = display.pinned;
+	otp.apply_defaults()?;
+	Ok((otp, display))
+}
+
+/// Set the icon from an inline `data:image/...;base64,` image, remote images are not fetched
+fn set_image(entry: &mut Entry, image: &str) {
+	let data = match image.strip_prefix("data:").and_then(|data| data.split_once(";base64,")) {
+		Some((mime, data)) if mime.starts_with("image/") => data,
+		_ => return eprintln!("Warning: ignoring image '{image}', only inline base64 images are supported"),
+	};
+	if let Err(e) = decode_base64(data).context("Invalid base64").and_then(|image| entry.set_icon(&image)) {
+		eprintln!("Warning: ignoring image of '{}': {e}", entry.label);
+	}
+}
+
+/// Build the otpauth URI of an Entry, the inverse of [`parse`]
+pub fn build(entry: &Entry) -> Result<String> {
+	let info = &entry.info;
+	let secret = match entry.method {
+		Method::Motp => hex::encode(&*info.secret_bytes()?),
+		_ => info.secret.clone(),
+	};
+	let issuer = entry.issuer.as_deref().filter(|issuer| !issuer.is_empty());
+	let label = match issuer {
+		Some(issuer) => format!("{}:{}", encode(issuer), encode(&entry.label)),
+		None => encode(&entry.label).into_owned(),
+	};
+	let mut uri = format!("otpauth://{}/{label}?secret={secret}", entry.method.to_string());
+	if let Some(issuer) = issuer {
+		uri += &format!("&issuer={}", encode(issuer));
+	}
+	uri += &format!("&algorithm={}&digits={}", info.algorithm.to_string(), info.digits);
+	if entry.method.is_event_based() {
+		uri += &format!("&counter={}", info.counter.unwrap_or_default());
+	} else if let Some(period) = info.period {
+		uri += &format!("&period={period}");
+	}
+	if let Some(pin) = &info.pin {
+		uri += &format!("&pin={}", encode(pin));
+	}
+	Ok(uri)
+}
+
+/// Decode base64 from a decoded query value, padding is optional
+///
+/// Generators often leave `+` unencoded, which the query decoding turned into a space.
+pub(crate) fn decode_base64(data: &str) -> Result<Vec<u8>, data_encoding::DecodeError> {
+	let data = data.replace(' ', "+");
+	data_encoding::BASE64_NOPAD.decode(data.trim_end_matches('=').as_bytes())
+}
+
+/// Split a decoded label into the optional issuer prefix and the account name
+pub(crate) fn split_label(label: &str) -> (Option<&str>, &str) {
+	match label.split_once(':') {
+		Some((issuer, account)) => {
+			let issuer = issuer.trim();
+			(if issuer.is_empty() { None } else { Some(issuer) }, account.trim_start())
+		}
+		None => (None, label),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn plain_label() {
+		let entry = parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+		assert_eq!(entry.label(), "alice");
+		assert_eq!(entry.issuer(), "GitHub");
+		assert_eq!(entry.method(), Method::TOTP);
+		assert_eq!(entry.secret(), "JBSWY3DPEHPK3PXP");
+	}
+
+	#[test]
+	fn canonical_secret() {
+		let entry = parse("otpauth://totp/alice?secret=jbsw%20y3dp%20ehpk%203pxp").unwrap();
+		assert_eq!(entry.secret(), "JBSWY3DPEHPK3PXP");
+	}
+
+	#[test]
+	fn issuer_from_label() {
+		let entry = parse("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+		assert_eq!(entry.label(), "alice");
+		assert_eq!(entry.issuer(), "GitHub");
+
+		let entry = parse("otpauth://totp/ACME%20Co%3A%20john.doe@email.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co").unwrap();
+		assert_eq!(entry.label(), "john.doe@email.com");
+		assert_eq!(entry.issuer(), "ACME Co");
+	}
+
+	#[test]
+	fn issuer_conflict() {
+		let entry = parse("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Other").unwrap();
+		assert_eq!(entry.label(), "alice");
+		assert_eq!(entry.issuer(), "Other");
+	}
+
+	#[test]
+	fn encoded_issuer_param() {
+		let entry = parse("otpauth://totp/bob?secret=JBSWY3DPEHPK3PXP&issuer=100%25%20Pure").unwrap();
+		assert_eq!(entry.issuer(), "100% Pure");
+	}
+
+	#[test]
+	fn vendor_keys() {
+		let entry = parse("otpauth://totp/Steam:gamer?secret=JBSWY3DPEHPK3PXP&issuer=Steam&color=red&lock=false&foo=bar").unwrap();
+		assert_eq!(entry.method(), Method::Steam);
+		assert_eq!(entry.digits(), Some(5));
+
+		let entry = parse("otpauth://totp/gamer?secret=JBSWY3DPEHPK3PXP&encoder=steam").unwrap();
+		assert_eq!(entry.method(), Method::Steam);
+		assert_eq!(entry.digits(), Some(5));
+
+		let entry = parse("otpauth://totp/bob?secret=JBSWY3DPEHPK3PXP&image=data%3Aimage%2Fpng%3Bbase64%2CiVBORw0K").unwrap();
+		assert_eq!(entry.thumbnail.as_deref(), Some("iVBORw0K"));
+		assert_eq!(entry.icon_mime.as_deref(), Some("image/png"));
+
+		let entry = parse("otpauth://hotp/bob?secret=JBSWY3DPEHPK3PXP&counter=7&image=https%3A%2F%2Fexample.com%2Flogo.png").unwrap();
+		assert_eq!(entry.counter(), Some(7));
+		assert_eq!(entry.thumbnail, None);
+	}
+
+	#[test]
+	fn defaults() {
+		let entry = parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+		assert_eq!(entry.algorithm(), Algorithm::SHA1);
+		assert_eq!(entry.digits(), Some(6));
+		assert_eq!(entry.period(), Some(30));
+
+		let entry = parse("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=sha256&digits=8").unwrap();
+		assert_eq!(entry.algorithm(), Algorithm::SHA256);
+		assert_eq!(entry.digits(), Some(8));
+		assert_eq!(entry.period(), None);
+	}
+
+	#[test]
+	fn motp() {
+		let entry = parse("otpauth://motp/Corp:bob?secret=0123456789ABCDEF&pin=1234&period=30").unwrap();
+		assert_eq!(entry.secret(), "AERUKZ4JVPG66");
+		assert_eq!(entry.pin(), Some("1234".to_string()));
+		assert_eq!(entry.algorithm(), Algorithm::MD5);
+		assert_eq!(entry.digits(), Some(6));
+		assert_eq!(entry.period(), Some(10));
+	}
+
+	#[test]
+	fn yandex() {
+		let entry = parse("otpauth://yandex/alice?secret=LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI&pin=7586").unwrap();
+		assert_eq!(entry.secret(), "LA2V6KMCGYMWWVEW64RNP3JA3I");
+		assert_eq!(entry.algorithm(), Algorithm::SHA256);
+		assert_eq!(entry.digits(), Some(8));
+		assert_eq!(entry.period(), Some(30));
+
+		assert!(parse("otpauth://yandex/alice?secret=LA2V6KMCGYMWWVEW64RNP3JA3I").is_err());
+	}
+
+	#[test]
+	fn base64_plus_as_space() {
+		assert_eq!(decode_base64("+/8=").unwrap(), vec![0xfb, 0xff]);
+		assert_eq!(decode_base64(" /8").unwrap(), vec![0xfb, 0xff]);
+		assert!(decode_base64("!").is_err());
+	}
+
+	#[test]
+	fn build_round_trip() {
+		let uris = [
+			"otpauth://totp/ACME%20Co:john.doe%40email.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60",
+			"otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&counter=7",
+			"otpauth://motp/Corp:bob?secret=0123456789abcdef&issuer=Corp&algorithm=MD5&digits=6&period=10&pin=1234",
+		];
+		for uri in uris {
+			assert_eq!(build(&parse(uri).unwrap()).unwrap(), uri);
+		}
+	}
+
+	#[test]
+	fn invalid() {
+		assert!(parse("https://example.com/alice?secret=JBSWY3DPEHPK3PXP").is_err());
+		assert!(parse("otpauth://xotp/alice?secret=JBSWY3DPEHPK3PXP").is_err());
+		assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=six").is_err());
+		assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=11").is_err());
+		assert!(parse("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&digits=40").is_err());
+		assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=10").is_ok());
+		assert!(parse("otpauth://totp/alice?issuer=GitHub").is_err());
+		assert!(parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PX!").is_err());
+	}
+
+	#[test]
+	fn ente_code_display() {
+		let ente = |display: &str| format!("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub&codeDisplay={}", encode(display));
+		let mut db = Database::default();
+		let added = parse_into(
+			&mut db,
+			&ente(r#"{"pinned":true,"trashed":false,"lastUsedAt":0,"tapCount":3,"tags":["Work","Dev"],"note":"main","position":0}"#),
+		);
+		assert_eq!(added.unwrap(), 1);
+		assert_eq!(db.entries[0].issuer(), "GitHub");
+		assert_eq!(db.entries[0].note, "main");
+		assert!(db.entries[0].favorite);
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Work", "Dev"]);
+
+		assert_eq!(parse_into(&mut db, &ente(r#"{"pinned":false,"trashed":true,"tags":[]}"#)).unwrap(), 0);
+		assert_eq!(db.entries.len(), 1);
+		assert!(parse(&ente("not json")).is_err());
+	}
+
+	#[test]
+	fn group_param() {
+		let mut db = Database::default();
+		parse_into(&mut db, "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&group=Work&group=Dev%20Ops").unwrap();
+		parse_into(&mut db, "otpauth://totp/bob?secret=JBSWY3DPEHPK3PXP&group=Work").unwrap();
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Work", "Dev Ops"]);
+		assert_eq!(db.entries[1].groups, vec![db.entries[0].groups[0].clone()]);
+	}
+}
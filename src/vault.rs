@@ -3,10 +3,8 @@
 //! Description of the Aegis Vault format:
 //! <https://github.com/beemdevelopment/Aegis/blob/master/docs/vault.md>
 //!
-//! This module does not convert all information from aegis, lost are:
-//!   note, icon, icon_mime, icon_hash, favorite, groups.
 //! When exporting to the aegis json format these are lost:
-//!   icon, url?, help url?, tags?
+//!   url?, help url?, tags?
 //!
 //! Exported files by this module can be decrypted by the python script in the aegis repository:
 //! <https://github.com/beemdevelopment/Aegis/blob/master/docs/decrypt.py>
@@ -14,12 +12,30 @@
 use aes_gcm::{KeyInit, aead::Aead};
 use anyhow::{Context, Result, anyhow};
 use rand::RngCore;
-use serde::{Deserialize, Serialize};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use ring::digest;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::algorithm::{Algorithm, Method};
+use crate::files;
+use crate::icon;
 
 const DB_VER: u32 = 3;
+const LOCK_RETRIES: u32 = 50;
+const MIN_SECRET_BITS: usize = 80;
+const RECOMMENDED_SECRET_BITS: usize = 128;
+const YANDEX_SECRET_LEN: usize = 16;
+const YANDEX_SECRET_FULL_LEN: usize = 26;
+
+/// Base32 without padding that ignores non-zero trailing bits, as Aegis and other authenticators do
+static BASE32: LazyLock<data_encoding::Encoding> = LazyLock::new(|| {
+	let mut spec = data_encoding::BASE32_NOPAD.specification();
+	spec.check_trailing_bits = false;
+	spec.encoding().expect("Base32 specification")
+});
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -105,9 +121,24 @@ impl Aegis {
 		password_slot.key = ciphertext.try_into().unwrap();
 
 		// Finally, we get the JSON string for the database and encrypt it.
+		self.encrypt_db(&master_key, header)
+	}
+
+	/// Encrypt the vault again with the master key returned by [`Aegis::decrypt`]
+	///
+	/// The slots of the header are kept, so the vault still opens with the same passwords. The nonce is renewed.
+	pub fn seal(&mut self, master_key: &[u8]) -> Result<()> {
+		let Self::Plaintext(plain_text) = self else {
+			anyhow::bail!("Seal can only be called on a plaintext object.");
+		};
+		let slots = plain_text.header.slots.take().context("No slots to seal the vault with")?;
+		self.encrypt_db(master_key, Header { params: Some(HeaderParam::default()), slots: Some(slots) })
+	}
+
+	fn encrypt_db(&mut self, master_key: &[u8], mut header: Header) -> Result<()> {
 		if let Self::Plaintext(plain_text) = self {
 			let db_json: Vec<u8> = serde_json::ser::to_string_pretty(&plain_text.db)?.as_bytes().to_vec();
-			let cipher = match aes_gcm::Aes256Gcm::new_from_slice(&master_key) {
+			let cipher = match aes_gcm::Aes256Gcm::new_from_slice(master_key) {
 				Ok(c) => c,
 				Err(_) => return Err(anyhow!("Could not create cipher from master key")),
 			};
@@ -126,6 +157,59 @@ impl Aegis {
 		Ok(())
 	}
 
+	/// Decrypt the vault in place with the first password slot that opens
+	///
+	/// The header is kept and the master key is returned, so the vault can be sealed again with [`Aegis::seal`].
+	pub fn decrypt(&mut self, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+		let Self::Encrypted(encrypted) = self else {
+			anyhow::bail!("Decrypt can only be called on an encrypted object.");
+		};
+		let master_key = encrypted
+			.header
+			.slots
+			.iter()
+			.flatten()
+			.filter(|slot| slot.type_ == 1)
+			.find_map(|slot| slot.decrypt_master_key(password).ok())
+			.map(Zeroizing::new)
+			.context("Did not find at least one slot with a valid key. Wrong password?")?;
+		let db = encrypted.decrypt_db(&master_key)?;
+		let header = std::mem::replace(&mut encrypted.header, Header { slots: None, params: None });
+		*self = Self::Plaintext(AegisPlainText { version: encrypted.version, header, db });
+		Ok(master_key)
+	}
+
+	/// The database of a plaintext (or decrypted) vault
+	pub fn database_mut(&mut self) -> Result<&mut Database> {
+		match self {
+			Self::Plaintext(plain_text) => Ok(&mut plain_text.db),
+			Self::Encrypted(_) => anyhow::bail!("The vault is encrypted"),
+		}
+	}
+
+	/// Write the vault to a temporary file only readable by the user next to `path` and rename it over `path`
+	pub fn write_atomic(&self, path: &Path) -> Result<()> {
+		let mut tmp = path.as_os_str().to_owned();
+		tmp.push(".tmp");
+		let tmp = PathBuf::from(tmp);
+		let _ = std::fs::remove_file(&tmp);
+		let json = serde_json::ser::to_string_pretty(&self)?;
+		let mut file = files::create(&tmp).with_context(|| format!("Cannot create {}", tmp.display()))?;
+		if let Err(e) = file.write_all(json.as_bytes()).and_then(|()| file.sync_all()).and_then(|()| std::fs::rename(&tmp, path)) {
+			let _ = std::fs::remove_file(&tmp);
+			return Err(e).with_context(|| format!("Cannot write {}", path.display()));
+		}
+		Ok(())
+	}
+
+	/// The database of a plaintext (or decrypted) vault
+	pub fn into_database(self) -> Result<Database> {
+		match self {
+			Self::Plaintext(plain_text) => Ok(plain_text.db),
+			Self::Encrypted(_) => anyhow::bail!("The vault is encrypted"),
+		}
+	}
+
 	pub fn restore_from_data(from: &[u8], key: Option<&str>) -> Result<Vec<Entry>> {
 		// TODO check whether file / database is encrypted by aegis
 		let aegis_root: Aegis = serde_json::de::from_slice(from)?;
@@ -134,7 +218,7 @@ impl Aegis {
 		// Check whether file is encrypted or in plaintext
 		match aegis_root {
 			Aegis::Plaintext(plain_text) => {
-				println!("Found unencrypted aegis vault with version {} and database version {}.", plain_text.version, plain_text.db.version);
+				eprintln!("Found unencrypted aegis vault with version {} and database version {}.", plain_text.version, plain_text.db.version);
 
 				// Check for correct aegis vault version and correct database version.
 				if plain_text.version != 1 {
@@ -152,7 +236,7 @@ impl Aegis {
 				}
 			}
 			Aegis::Encrypted(encrypted) => {
-				println!("Found encrypted aegis vault with version {}.", encrypted.version);
+				eprintln!("Found encrypted aegis vault with version {}.", encrypted.version);
 
 				// Check for correct aegis vault version and whether a password was supplied.
 				if encrypted.version != 1 {
@@ -161,12 +245,6 @@ impl Aegis {
 					anyhow::bail!("Found encrypted aegis database but no password given.");
 				}
 
-				// Ciphertext is stored in base64, we have to decode it.
-				let mut ciphertext = data_encoding::BASE64.decode(encrypted.db.as_bytes()).context("Cannot decode (base64) encoded database")?;
-
-				// Add the encryption tag
-				ciphertext.append(&mut encrypted.header.params.as_ref().unwrap().tag.into());
-
 				// Find slots with type password and derive the corresponding key. This key is
 				// used to decrypt the master key which in turn can be used to
 				// decrypt the database.
@@ -178,38 +256,8 @@ impl Aegis {
 					.iter()
 					.filter(|slot| slot.type_ == 1) // We don't handle biometric slots for now
 					.map(|slot| -> Result<Vec<u8>> {
-						println!("Found possible master key with UUID {}.", slot.uuid);
-
-						// Create parameters for scrypt function and derive decryption key for
-						// master key
-						//
-						// Somehow, scrypt errors do not implement StdErr and cannot be converted to
-						// anyhow::Error. Should be possible but don't know why it doesn't work.
-						let params = scrypt::Params::new(
-							// TODO log2 for u64 is not stable yet. Change this in the future.
-							(slot.n() as f64).log2() as u8, // Defaults to 15 by aegis
-							slot.r(),                       // Defaults to 8 by aegis
-							slot.p(),                       // Defaults to 1 by aegis
-							scrypt::Params::RECOMMENDED_LEN,
-						)
-						.map_err(|_| anyhow::anyhow!("Invalid scrypt parameters"))?;
-						let mut temp_key: [u8; 32] = [0u8; 32];
-						scrypt::scrypt(key.unwrap().as_bytes(), slot.salt(), &params, &mut temp_key)
-							.map_err(|_| anyhow::anyhow!("Scrypt key derivation failed"))?;
-
-						// Now, try to decrypt the master key.
-						let cipher = match aes_gcm::Aes256Gcm::new_from_slice(&temp_key) {
-							Ok(c) => c,
-							Err(_) => return Err(anyhow!("Could not create cipher from key")),
-						};
-						let mut ciphertext: Vec<u8> = slot.key.to_vec();
-						ciphertext.append(&mut slot.key_params.tag.to_vec());
-
-						// Here we get the master key. The decrypt function does not return an error
-						// implementing std error. Thus, we have to convert it.
-						cipher
-							.decrypt(aes_gcm::Nonce::from_slice(&slot.key_params.nonce), ciphertext.as_ref())
-							.map_err(|_| anyhow::anyhow!("Cannot decrypt master key"))
+						eprintln!("Found possible master key with UUID {}.", slot.uuid);
+						slot.decrypt_master_key(key.unwrap())
 					})
 					// Here, we don't want to fail the whole function because one key slot failed to
 					// get the correct master key. Maybe there is another slot we were able to
@@ -217,7 +265,7 @@ impl Aegis {
 					.filter_map(|x| match x {
 						Ok(x) => Some(x),
 						Err(e) => {
-							println!("Decrypting master key failed: {:?}", e);
+							eprintln!("Decrypting master key failed: {:?}", e);
 							None
 						}
 					})
@@ -225,30 +273,20 @@ impl Aegis {
 
 				// Choose the first valid master key. I don't think there are aegis
 				// installations with two valid password slots.
-				println!("Found {} valid password slots / master keys.", master_keys.len());
+				eprintln!("Found {} valid password slots / master keys.", master_keys.len());
 				let master_key = match master_keys.first() {
 					Some(x) => {
-						println!("Using only the first valid key slot / master key.");
+						eprintln!("Using only the first valid key slot / master key.");
 						x
 					}
 					None => anyhow::bail!("Did not find at least one slot with a valid key. Wrong password?"),
 				};
 
 				// Try to decrypt the database with this master key.
-				let cipher = match aes_gcm::Aes256Gcm::new_from_slice(master_key) {
-					Ok(c) => c,
-					Err(_) => return Err(anyhow!("Could not create cipher from key")),
-				};
-				let plaintext = cipher
-					.decrypt(aes_gcm::Nonce::from_slice(&encrypted.header.params.as_ref().unwrap().nonce), ciphertext.as_ref())
-					// Decrypt does not return an error implementing std error, thus we convert it.
-					.map_err(|_| anyhow::anyhow!("Cannot decrypt database"))?;
-
-				// Now, we have the decrypted string. Trying to load it with JSON.
-				let db: Database = serde_json::de::from_slice(&plaintext).context("Deserialize decrypted database failed")?;
+				let db = encrypted.decrypt_db(master_key)?;
 
 				// Check version of the database
-				println!("Found aegis database with version {}.", db.version);
+				eprintln!("Found aegis database with version {}.", db.version);
 				//PP Version 3 is current...
 				//if encrypted.version > 2 {
 				//	anyhow::bail!("Aegis database version expected to be 1 or 2. Found {} instead.", db.version);
@@ -275,6 +313,87 @@ impl Aegis {
 	}
 }
 
+impl AegisEncrypted {
+	/// Decrypt the database with the master key
+	fn decrypt_db(&self, master_key: &[u8]) -> Result<Database> {
+		let params = self.header.params.as_ref().context("Missing header params")?;
+
+		// Ciphertext is stored in base64, we have to decode it.
+		let mut ciphertext = data_encoding::BASE64.decode(self.db.as_bytes()).context("Cannot decode (base64) encoded database")?;
+
+		// Add the encryption tag
+		ciphertext.extend_from_slice(&params.tag);
+
+		let cipher = match aes_gcm::Aes256Gcm::new_from_slice(master_key) {
+			Ok(c) => c,
+			Err(_) => return Err(anyhow!("Could not create cipher from key")),
+		};
+		let plaintext = Zeroizing::new(
+			cipher
+				.decrypt(aes_gcm::Nonce::from_slice(&params.nonce), ciphertext.as_ref())
+				// Decrypt does not return an error implementing std error, thus we convert it.
+				.map_err(|_| anyhow::anyhow!("Cannot decrypt database"))?,
+		);
+
+		// Now, we have the decrypted string. Trying to load it with JSON.
+		serde_json::de::from_slice(&plaintext).context("Deserialize decrypted database failed")
+	}
+}
+
+/// Lock file next to a vault, so concurrent writers wait for each other
+///
+/// The lock file holds the PID of its process and is removed when the lock is dropped.
+pub struct VaultLock {
+	path: PathBuf,
+}
+
+impl VaultLock {
+	/// Create `path.lock`, retrying for a few seconds while another process holds it
+	///
+	/// A lock left behind by a process that no longer runs is removed.
+	pub fn acquire(path: &Path) -> Result<Self> {
+		let mut lock = path.as_os_str().to_owned();
+		lock.push(".lock");
+		let lock = PathBuf::from(lock);
+		for _ in 0..LOCK_RETRIES {
+			match std::fs::OpenOptions::new().write(true).create_new(true).open(&lock) {
+				Ok(mut file) => {
+					let lock = Self { path: lock };
+					write!(file, "{}", std::process::id()).with_context(|| format!("Cannot write lock file {}", lock.path.display()))?;
+					return Ok(lock);
+				}
+				Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+					if lock_owner(&lock).is_some_and(|pid| !process_exists(pid)) {
+						eprintln!("Removing stale lock file {}", lock.display());
+						let _ = std::fs::remove_file(&lock);
+						continue;
+					}
+					std::thread::sleep(std::time::Duration::from_millis(100));
+				}
+				Err(e) => return Err(e).with_context(|| format!("Cannot create lock file {}", lock.display())),
+			}
+		}
+		let owner = lock_owner(&lock).map(|pid| format!("process {pid}")).unwrap_or_else(|| "another process".to_string());
+		anyhow::bail!("Vault is locked by {owner}, remove {} if it is stale", lock.display())
+	}
+}
+
+/// The PID written into a lock file
+fn lock_owner(lock: &Path) -> Option<u32> {
+	std::fs::read_to_string(lock).ok()?.trim().parse().ok()
+}
+
+/// Whether a process runs, assumed when it cannot be checked
+fn process_exists(pid: u32) -> bool {
+	if cfg!(target_os = "linux") { Path::new("/proc").join(pid.to_string()).exists() } else { true }
+}
+
+impl Drop for VaultLock {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.path);
+	}
+}
+
 /// Header of the Encrypted Aegis JSON File
 ///
 /// Contains all necessary information for encrypting / decrypting the vault (db
@@ -335,6 +454,39 @@ impl HeaderSlot {
 	pub fn salt(&self) -> &[u8; 32] {
 		&self.salt
 	}
+
+	/// Derive the key from the password and decrypt the master key of this slot
+	fn decrypt_master_key(&self, password: &str) -> Result<Vec<u8>> {
+		// Create parameters for scrypt function and derive decryption key for
+		// master key
+		//
+		// Somehow, scrypt errors do not implement StdErr and cannot be converted to
+		// anyhow::Error. Should be possible but don't know why it doesn't work.
+		let params = scrypt::Params::new(
+			// TODO log2 for u64 is not stable yet. Change this in the future.
+			(self.n() as f64).log2() as u8, // Defaults to 15 by aegis
+			self.r(),                       // Defaults to 8 by aegis
+			self.p(),                       // Defaults to 1 by aegis
+			scrypt::Params::RECOMMENDED_LEN,
+		)
+		.map_err(|_| anyhow::anyhow!("Invalid scrypt parameters"))?;
+		let mut temp_key = Zeroizing::new([0u8; 32]);
+		scrypt::scrypt(password.as_bytes(), self.salt(), &params, temp_key.as_mut()).map_err(|_| anyhow::anyhow!("Scrypt key derivation failed"))?;
+
+		// Now, try to decrypt the master key.
+		let cipher = match aes_gcm::Aes256Gcm::new_from_slice(temp_key.as_ref()) {
+			Ok(c) => c,
+			Err(_) => return Err(anyhow!("Could not create cipher from key")),
+		};
+		let mut ciphertext: Vec<u8> = self.key.to_vec();
+		ciphertext.append(&mut self.key_params.tag.to_vec());
+
+		// Here we get the master key. The decrypt function does not return an error
+		// implementing std error. Thus, we have to convert it.
+		cipher
+			.decrypt(aes_gcm::Nonce::from_slice(&self.key_params.nonce), ciphertext.as_ref())
+			.map_err(|_| anyhow::anyhow!("Cannot decrypt master key"))
+	}
 }
 
 impl Default for HeaderSlot {
@@ -379,13 +531,116 @@ impl Default for HeaderParam {
 pub struct Database {
 	pub version: u32,
 	pub entries: Vec<Entry>,
-	pub groups: Option<Vec<bool>>, // No use specifying the actual struct, so use bool
+	#[serde(default, deserialize_with = "null_as_default")]
+	pub groups: Vec<Group>,
 }
 
 impl Default for Database {
 	fn default() -> Self {
-		Self { version: DB_VER, entries: std::vec::Vec::new(), groups: None }
+		Self { version: DB_VER, entries: std::vec::Vec::new(), groups: Vec::new() }
+	}
+}
+
+impl From<Database> for Aegis {
+	fn from(db: Database) -> Self {
+		Self::Plaintext(AegisPlainText { db, ..Default::default() })
+	}
+}
+
+impl Database {
+	/// The UUID of the group with this name, the group is created when missing
+	pub fn group_uuid(&mut self, name: &str) -> String {
+		if let Some(group) = self.groups.iter().find(|group| group.name == name) {
+			return group.uuid.clone();
+		}
+		let group = Group { uuid: uuid::Uuid::new_v4().to_string(), name: name.to_string() };
+		let uuid = group.uuid.clone();
+		self.groups.push(group);
+		uuid
+	}
+
+	/// The names of the groups of an entry
+	pub fn group_names(&self, entry: &Entry) -> Vec<String> {
+		self.groups.iter().filter(|group| entry.groups.contains(&group.uuid)).map(|group| group.name.clone()).collect()
+	}
+
+	fn group(&self, name: &str) -> Result<&Group> {
+		self.groups.iter().find(|group| group.name == name).with_context(|| format!("No group named '{name}'"))
+	}
+
+	fn entry_mut(&mut self, index: usize) -> Result<&mut Entry> {
+		self.entries.get_mut(index).with_context(|| format!("No entry {index}"))
+	}
+
+	/// Create a group, returning its UUID
+	pub fn add_group(&mut self, name: &str) -> Result<String> {
+		if name.trim().is_empty() {
+			anyhow::bail!("The group name is empty");
+		}
+		if self.group(name).is_ok() {
+			anyhow::bail!("Group '{name}' already exists");
+		}
+		Ok(self.group_uuid(name))
+	}
+
+	pub fn rename_group(&mut self, name: &str, new_name: &str) -> Result<()> {
+		if new_name.trim().is_empty() {
+			anyhow::bail!("The group name is empty");
+		}
+		if name != new_name && self.group(new_name).is_ok() {
+			anyhow::bail!("Group '{new_name}' already exists");
+		}
+		let group = self.groups.iter_mut().find(|group| group.name == name).with_context(|| format!("No group named '{name}'"))?;
+		group.name = new_name.to_string();
+		Ok(())
+	}
+
+	/// Delete a group and remove it from its entries, the entries are kept
+	pub fn delete_group(&mut self, name: &str) -> Result<()> {
+		let uuid = self.group(name)?.uuid.clone();
+		self.groups.retain(|group| group.uuid != uuid);
+		for entry in &mut self.entries {
+			entry.groups.retain(|group| *group != uuid);
+		}
+		Ok(())
 	}
+
+	/// Add the entry at index `entry` to a group, the group is created when missing
+	///
+	/// Returns false when the entry already was in the group.
+	pub fn assign_group(&mut self, entry: usize, name: &str) -> Result<bool> {
+		self.entry_mut(entry)?;
+		let uuid = self.group_uuid(name);
+		let entry = self.entry_mut(entry)?;
+		if entry.groups.contains(&uuid) {
+			return Ok(false);
+		}
+		entry.groups.push(uuid);
+		Ok(true)
+	}
+
+	/// Remove the entry at index `entry` from a group
+	///
+	/// Returns false when the entry was not in the group.
+	pub fn unassign_group(&mut self, entry: usize, name: &str) -> Result<bool> {
+		let uuid = self.group(name)?.uuid.clone();
+		let entry = self.entry_mut(entry)?;
+		let count = entry.groups.len();
+		entry.groups.retain(|group| *group != uuid);
+		Ok(entry.groups.len() != count)
+	}
+}
+
+/// A named group, entries refer to it by UUID (database version 3)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+	pub uuid: String,
+	pub name: String,
+}
+
+/// Vaults written before groups were supported contain `"groups": null`
+fn null_as_default<'de, D: Deserializer<'de>, T: Default + Deserialize<'de>>(deserializer: D) -> Result<T, D::Error> {
+	Ok(Option::deserialize(deserializer)?.unwrap_or_default())
 }
 
 /// An OTP Entry
@@ -403,16 +658,28 @@ pub struct Entry {
 
 	pub issuer: Option<String>,
 
-	// TODO tags are not imported/exported right now.
-	#[serde(rename = "groups")]
-	pub tags: Option<String>,
+	/// The UUIDs of the groups of the entry
+	#[serde(default, deserialize_with = "null_as_default")]
+	pub groups: Vec<String>,
+
+	#[serde(default)]
+	pub note: String,
 
-	// Note is omitted
-	// Icon:
-	// TODO: Aegis encodes icons as JPEG's encoded in Base64 with padding. Does authenticator support this?
+	#[serde(default)]
+	pub favorite: bool,
+
+	/// The base64 encoded icon image
 	#[serde(rename = "icon")]
 	pub thumbnail: Option<String>,
 
+	/// The MIME type of the icon, see [`crate::icon`]
+	#[serde(default)]
+	pub icon_mime: Option<String>,
+
+	/// The hex SHA-256 hash of the icon image
+	#[serde(default)]
+	pub icon_hash: Option<String>,
+
 	/// The configuration of the algorithm
 	pub info: Detail,
 }
@@ -462,6 +729,69 @@ impl Entry {
 	pub fn counter(&self) -> Option<u32> {
 		self.info.counter
 	}
+
+	pub fn pin(&self) -> Option<String> {
+		self.info.pin.clone()
+	}
+
+	/// Whether issuer or label contain the filter, ignoring case
+	pub fn matches(&self, filter: &str) -> bool {
+		let filter = filter.to_lowercase();
+		self.issuer().to_lowercase().contains(&filter) || self.label.to_lowercase().contains(&filter)
+	}
+
+	/// Set the icon to an SVG, PNG or JPEG image
+	pub fn set_icon(&mut self, image: &[u8]) -> Result<()> {
+		let mime = icon::mime(image).context("The icon is not an SVG, PNG or JPEG image")?;
+		self.thumbnail = Some(data_encoding::BASE64.encode(image));
+		self.icon_mime = Some(mime.to_string());
+		self.icon_hash = Some(hex::encode(digest::digest(&digest::SHA256, image)));
+		Ok(())
+	}
+
+	/// The decoded icon image
+	pub fn icon(&self) -> Result<Option<Vec<u8>>> {
+		self.thumbnail
+			.as_ref()
+			.map(|icon| data_encoding::BASE64.decode(icon.as_bytes()).context("The icon is not valid base64"))
+			.transpose()
+	}
+
+	pub fn strip_icon(&mut self) {
+		self.thumbnail = None;
+		self.icon_mime = None;
+		self.icon_hash = None;
+	}
+
+	/// Set missing digits and period to the defaults of the method
+	///
+	/// mOTP always uses MD5 with 6 digits and period 10, Yandex always uses SHA256 with 8 digits.
+	/// Fails when the digits are out of range for the method.
+	pub fn apply_defaults(&mut self) -> Result<()> {
+		let method = self.method;
+		if self.info.digits == 0 {
+			self.info.digits = method.default_digits();
+		}
+		if self.info.period.is_none() {
+			self.info.period = method.default_period();
+		}
+		if method == Method::Motp {
+			if self.info.algorithm != Algorithm::MD5 || self.info.digits != 6 || self.info.period != Some(10) {
+				eprintln!("Warning: mOTP entry {} always uses MD5 with 6 digits and period 10", self.label);
+			}
+			self.info.algorithm = Algorithm::MD5;
+			self.info.digits = 6;
+			self.info.period = Some(10);
+		}
+		if method == Method::Yandex {
+			if self.info.algorithm != Algorithm::SHA256 || self.info.digits != 8 {
+				eprintln!("Warning: Yandex entry {} always uses SHA256 with 8 digits", self.label);
+			}
+			self.info.algorithm = Algorithm::SHA256;
+			self.info.digits = 8;
+		}
+		self.info.check_digits(method).with_context(|| format!("Invalid entry {}", self.label))
+	}
 }
 
 /// OTP Entry Details
@@ -477,6 +807,109 @@ pub struct Detail {
 	pub period: Option<u32>,
 	#[zeroize(skip)]
 	pub counter: Option<u32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub pin: Option<String>,
+}
+
+impl Detail {
+	/// Check the digits are between 1 and the maximum of the method
+	pub fn check_digits(&self, method: Method) -> Result<()> {
+		if !(1..=method.max_digits()).contains(&self.digits) {
+			anyhow::bail!("Invalid digits {} for {}, expected 1 to {}", self.digits, method.to_string(), method.max_digits());
+		}
+		Ok(())
+	}
+
+	/// Validate a base32 secret and store it in canonical form
+	///
+	/// Lowercase is uppercased and spaces, dashes and `=` padding are stripped before decoding.
+	/// Secrets shorter than 80 bits are rejected, shorter than 128 bits (RFC 4226) give a warning.
+	pub fn set_secret(&mut self, secret: &str) -> Result<()> {
+		let canonical = Zeroizing::new(secret.chars().filter(|c| !matches!(c, ' ' | '-' | '=')).collect::<String>().to_uppercase());
+		if canonical.is_empty() {
+			anyhow::bail!("Empty secret");
+		}
+		let bytes = decode_base32(&canonical)?;
+		let bits = bytes.len() * 8;
+		if bits < MIN_SECRET_BITS {
+			anyhow::bail!("Secret is too short: {bits} bits, at least {MIN_SECRET_BITS} needed");
+		}
+		if bits < RECOMMENDED_SECRET_BITS {
+			eprintln!("Warning: secret is only {bits} bits, at least {RECOMMENDED_SECRET_BITS} is recommended");
+		}
+		self.secret.zeroize();
+		self.secret = BASE32.encode(&bytes);
+		Ok(())
+	}
+
+	/// Store a hexadecimal secret (as used by mOTP) in base32
+	pub fn set_hex_secret(&mut self, secret: &str) -> Result<()> {
+		let bytes = Zeroizing::new(hex::decode(secret.trim()).map_err(|_| anyhow!("Secret is not valid hex"))?);
+		if bytes.is_empty() {
+			anyhow::bail!("Empty secret");
+		}
+		self.secret.zeroize();
+		self.secret = data_encoding::BASE32_NOPAD.encode(&bytes);
+		Ok(())
+	}
+
+	/// Validate a base32 Yandex secret and store its first 16 bytes
+	///
+	/// Secrets from a QR code are 16 bytes, secrets entered by hand are 26 bytes and end with a 12-bit checksum.
+	pub fn set_yandex_secret(&mut self, secret: &str) -> Result<()> {
+		let canonical = Zeroizing::new(secret.chars().filter(|c| !matches!(c, ' ' | '-' | '=')).collect::<String>().to_uppercase());
+		let bytes = decode_base32(&canonical)?;
+		match bytes.len() {
+			YANDEX_SECRET_LEN => (),
+			YANDEX_SECRET_FULL_LEN if yandex_checksum_valid(&bytes) => (),
+			YANDEX_SECRET_FULL_LEN => anyhow::bail!("Yandex secret checksum invalid"),
+			len => anyhow::bail!("Invalid Yandex secret length: {len} bytes"),
+		}
+		self.secret.zeroize();
+		self.secret = data_encoding::BASE32_NOPAD.encode(&bytes[..YANDEX_SECRET_LEN]);
+		Ok(())
+	}
+
+	/// The decoded secret
+	pub fn secret_bytes(&self) -> Result<Zeroizing<Vec<u8>>> {
+		decode_base32(self.secret.trim_end_matches('='))
+	}
+}
+
+fn decode_base32(secret: &str) -> Result<Zeroizing<Vec<u8>>> {
+	Ok(Zeroizing::new(BASE32.decode(secret.as_bytes()).map_err(|_| anyhow!("Secret is not valid base32"))?))
+}
+
+/// Check the 12-bit checksum at the end of a full Yandex secret (ported from Aegis' YandexInfo)
+fn yandex_checksum_valid(secret: &[u8]) -> bool {
+	let len = secret.len();
+	let original = (u16::from(secret[len - 2]) & 0x0f) << 8 | u16::from(secret[len - 1]);
+	let mut accum: u16 = 0;
+	let mut accum_bits = 0;
+	let mut total_bits = len * 8 - 12;
+	let mut index = 0;
+	let mut available_bits = 8;
+	while total_bits > 0 {
+		let mut required_bits = (13 - accum_bits).min(total_bits);
+		while required_bits > 0 {
+			let bits = required_bits.min(available_bits);
+			let input = (u16::from(secret[index]) & ((1 << available_bits) - 1)) >> (available_bits - bits);
+			accum = accum << bits | input;
+			total_bits -= bits;
+			required_bits -= bits;
+			available_bits -= bits;
+			accum_bits += bits;
+			if available_bits == 0 {
+				index += 1;
+				available_bits = 8;
+			}
+		}
+		if accum_bits == 13 {
+			accum ^= 0b1_1000_1111_0011;
+		}
+		accum_bits = 16 - accum.leading_zeros() as usize;
+	}
+	accum == original
 }
 
 #[cfg(test)]
@@ -562,25 +995,213 @@ mod tests {
 		assert_eq!(entries[6].method(), Method::Steam);
 	}
 
+	#[test]
+	fn secret_normalisation() {
+		let mut detail = Detail::default();
+		detail.set_secret("jbsw y3dp-ehpk 3pxp").unwrap();
+		assert_eq!(detail.secret, "JBSWY3DPEHPK3PXP");
+		detail.set_secret("KUVJJOM753IHTNDSZVCNKL7GII======").unwrap();
+		assert_eq!(detail.secret, "KUVJJOM753IHTNDSZVCNKL7GII");
+
+		assert!(detail.set_secret("").is_err());
+		assert!(detail.set_secret("= =").is_err());
+		assert!(detail.set_secret("JBSWY3DPEHPK3PX1").is_err());
+		assert!(detail.set_secret("JBSWY3DP").is_err());
+		assert_eq!(detail.secret, "KUVJJOM753IHTNDSZVCNKL7GII");
+	}
+
+	#[test]
+	fn secret_trailing_bits() {
+		// The last character carries two unused bits, which some generators leave non-zero
+		let mut detail = Detail::default();
+		detail.set_secret("JRZCL47CMXVOQMNPZR2F7J4RGJ").unwrap();
+		assert_eq!(detail.secret, "JRZCL47CMXVOQMNPZR2F7J4RGI");
+		detail.secret = "JRZCL47CMXVOQMNPZR2F7J4RGJ".to_string();
+		assert_eq!(detail.secret_bytes().unwrap().len(), 16);
+		detail.set_yandex_secret("JRZCL47CMXVOQMNPZR2F7J4RGJ").unwrap();
+		assert_eq!(detail.secret, "JRZCL47CMXVOQMNPZR2F7J4RGI");
+	}
+
+	#[test]
+	fn motp_round_trip() {
+		let mut entry = Entry { method: Method::Motp, label: "Bob".to_string(), issuer: Some("Corp".to_string()), ..Default::default() };
+		entry.info.set_hex_secret("0123456789abcdef").unwrap();
+		entry.info.pin = Some("1234".to_string());
+		entry.info.algorithm = Algorithm::SHA256;
+		entry.apply_defaults().unwrap();
+		assert_eq!(entry.secret(), "AERUKZ4JVPG66");
+
+		let json = serde_json::to_string(&entry).unwrap();
+		assert!(json.contains(r#""type":"motp""#));
+		assert!(json.contains(r#""algo":"MD5""#));
+		assert!(json.contains(r#""pin":"1234""#));
+
+		let entry: Entry = serde_json::from_str(&json).unwrap();
+		assert_eq!(entry.method(), Method::Motp);
+		assert_eq!(entry.algorithm(), Algorithm::MD5);
+		assert_eq!(entry.digits(), Some(6));
+		assert_eq!(entry.period(), Some(10));
+		assert_eq!(entry.pin(), Some("1234".to_string()));
+	}
+
+	#[test]
+	fn yandex_secret() {
+		let mut detail = Detail::default();
+		detail.set_yandex_secret("LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI").unwrap();
+		assert_eq!(detail.secret, "LA2V6KMCGYMWWVEW64RNP3JA3I");
+		detail.set_yandex_secret("la2v6kmcgymwwvew64rnp3ja3i").unwrap();
+		assert_eq!(detail.secret, "LA2V6KMCGYMWWVEW64RNP3JA3I");
+
+		assert!(detail.set_yandex_secret("LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPA").is_err());
+		assert!(detail.set_yandex_secret("JBSWY3DPEHPK3PXP").is_err());
+	}
+
+	#[test]
+	fn unknown_type() {
+		let data = std::fs::read_to_string("./test_databases/aegis_plain.json").unwrap();
+		let data = data.replace(r#""type": "steam""#, r#""type": "unknown""#);
+		assert!(Aegis::restore_from_data(data.as_bytes(), None).is_err());
+	}
+
+	#[test]
+	fn reseal() {
+		let data = std::fs::read_to_string("./test_databases/aegis_encrypted.json").unwrap();
+		let mut vault: Aegis = serde_json::from_str(&data).unwrap();
+		let Aegis::Encrypted(encrypted) = &vault else { panic!("Expected encrypted vault") };
+		let slot_uuid = encrypted.header.slots.as_ref().unwrap()[0].uuid.clone();
+
+		let master_key = vault.decrypt("test").unwrap();
+		vault.database_mut().unwrap().entries[3].info.counter = Some(2);
+		vault.seal(&master_key).unwrap();
+
+		let Aegis::Encrypted(encrypted) = &vault else { panic!("Expected encrypted vault") };
+		assert_eq!(encrypted.header.slots.as_ref().unwrap()[0].uuid, slot_uuid);
+		let raw = serde_json::to_string(&vault).unwrap();
+		let entries = Aegis::restore_from_data(raw.as_bytes(), Some("test")).unwrap();
+		assert_eq!(entries[3].counter(), Some(2));
+		assert!(Aegis::default().database_mut().is_ok());
+		assert!(vault.decrypt("wrong").is_err());
+	}
+
+	#[test]
+	fn groups() {
+		let db: Database = serde_json::from_str(r#"{"version": 3, "entries": [], "groups": null}"#).unwrap();
+		assert!(db.groups.is_empty());
+
+		let mut db = Database::default();
+		let work = db.group_uuid("Work");
+		assert_eq!(db.group_uuid("Work"), work);
+		let entry = Entry { groups: vec![work, db.group_uuid("Home")], ..Default::default() };
+		assert_eq!(db.group_names(&entry), vec!["Work", "Home"]);
+
+		let json = serde_json::to_string(&entry).unwrap();
+		let entry: Entry = serde_json::from_str(&json).unwrap();
+		assert_eq!(entry.groups.len(), 2);
+	}
+
+	#[test]
+	fn manage_groups() {
+		let mut db = Database::default();
+		db.entries.push(Entry::default());
+		db.entries.push(Entry::default());
+		db.add_group("Work").unwrap();
+		assert!(db.add_group("Work").is_err());
+		assert!(db.add_group(" ").is_err());
+
+		assert!(db.assign_group(0, "Work").unwrap());
+		assert!(!db.assign_group(0, "Work").unwrap());
+		assert!(db.assign_group(1, "Home").unwrap());
+		assert!(db.assign_group(2, "Work").is_err());
+		assert_eq!(db.group_names(&db.entries[1]), vec!["Home"]);
+
+		db.rename_group("Work", "Job").unwrap();
+		assert!(db.rename_group("Job", "Home").is_err());
+		assert!(db.rename_group("Work", "Other").is_err());
+		assert_eq!(db.group_names(&db.entries[0]), vec!["Job"]);
+
+		assert!(db.unassign_group(0, "Job").unwrap());
+		assert!(!db.unassign_group(0, "Job").unwrap());
+		assert!(db.unassign_group(0, "Nope").is_err());
+
+		db.delete_group("Home").unwrap();
+		assert!(db.entries[1].groups.is_empty());
+		assert_eq!(db.groups.len(), 1);
+		assert!(db.delete_group("Home").is_err());
+	}
+
+	#[test]
+	fn assign_group_without_uuids() {
+		// Both entries share the empty UUID, so only the index tells them apart
+		let mut db = Database::default();
+		db.entries.push(Entry { label: "alice".to_string(), ..Default::default() });
+		db.entries.push(Entry { label: "bob".to_string(), ..Default::default() });
+		assert!(db.assign_group(1, "Work").unwrap());
+		assert!(db.entries[0].groups.is_empty());
+		assert_eq!(db.group_names(&db.entries[1]), vec!["Work"]);
+
+		db.assign_group(0, "Home").unwrap();
+		assert!(db.unassign_group(0, "Home").unwrap());
+		assert!(db.entries[0].groups.is_empty());
+		assert_eq!(db.group_names(&db.entries[1]), vec!["Work"]);
+	}
+
+	#[test]
+	fn lock() {
+		let path = std::env::temp_dir().join(format!("aegisvault-lock-{}.json", std::process::id()));
+		let lock = VaultLock::acquire(&path).unwrap();
+		assert!(path.with_extension("json.lock").exists());
+		drop(lock);
+		assert!(!path.with_extension("json.lock").exists());
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn stale_lock() {
+		let path = std::env::temp_dir().join(format!("aegisvault-stale-{}.json", std::process::id()));
+		// Above the largest PID of Linux, so no such process runs
+		std::fs::write(path.with_extension("json.lock"), "4294967295").unwrap();
+		let lock = VaultLock::acquire(&path).unwrap();
+		assert_eq!(std::fs::read_to_string(path.with_extension("json.lock")).unwrap(), std::process::id().to_string());
+		drop(lock);
+	}
+
+	#[test]
+	fn write_failure() {
+		// A directory in the way makes the rename fail, the temporary file must not stay behind
+		let path = std::env::temp_dir().join(format!("aegisvault-write-dir-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		std::fs::create_dir_all(path.join("entry")).unwrap();
+		assert!(Aegis::default().write_atomic(&path).is_err());
+		assert!(!path.with_extension("tmp").exists());
+		std::fs::remove_dir_all(&path).unwrap();
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn write_private() {
+		use std::os::unix::fs::PermissionsExt;
+		let path = std::env::temp_dir().join(format!("aegisvault-write-{}.json", std::process::id()));
+		std::fs::write(&path, "{}").unwrap();
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+		Aegis::default().write_atomic(&path).unwrap();
+		assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+		assert!(serde_json::from_slice::<Aegis>(&std::fs::read(&path).unwrap()).is_ok());
+		std::fs::remove_file(&path).unwrap();
+	}
+
 	#[test]
 	fn encrypt() {
 		let mut aegis_root = Aegis::default();
 		let password = "my-super-secure-password";
 
-		let mut otp_entry = Entry::default();
-		otp_entry.method = Method::TOTP;
-		otp_entry.label = "Mason".to_string();
-		otp_entry.issuer = Some("Deno".to_string());
+		let mut otp_entry = Entry { method: Method::TOTP, label: "Mason".to_string(), issuer: Some("Deno".to_string()), ..Default::default() };
 		otp_entry.info.secret = "4SJHB4GSD43FZBAI7C2HLRJGPQ".to_string();
 		otp_entry.info.period = Some(30);
 		otp_entry.info.digits = 6;
 		otp_entry.info.counter = None;
 		aegis_root.add_entry(otp_entry);
 
-		let mut otp_entry = Entry::default();
-		otp_entry.method = Method::HOTP;
-		otp_entry.label = "James".to_string();
-		otp_entry.issuer = Some("Issuu".to_string());
+		let mut otp_entry = Entry { method: Method::HOTP, label: "James".to_string(), issuer: Some("Issuu".to_string()), ..Default::default() };
 		otp_entry.info.secret = "YOOMIXWS5GN6RTBPUFFWKTW5M4".to_string();
 		otp_entry.info.algorithm = Algorithm::SHA1;
 		otp_entry.info.period = None;